@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS videos (
+    url TEXT PRIMARY KEY,
+    file_id TEXT NOT NULL
+);
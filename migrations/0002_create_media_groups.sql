@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS media_groups (
+    url TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    file_id TEXT NOT NULL,
+    PRIMARY KEY (url, position)
+);
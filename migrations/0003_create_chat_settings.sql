@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS chat_settings (
+    chat_id INTEGER PRIMARY KEY,
+    auto_download INTEGER NOT NULL DEFAULT 0,
+    caption_style TEXT NOT NULL DEFAULT 'full',
+    quality TEXT NOT NULL DEFAULT 'high',
+    delete_original INTEGER NOT NULL DEFAULT 0,
+    allowed_platforms TEXT
+);
@@ -0,0 +1,14 @@
+ALTER TABLE videos ADD COLUMN kind TEXT NOT NULL DEFAULT 'video';
+ALTER TABLE videos ADD COLUMN file_unique_id TEXT;
+ALTER TABLE videos ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE videos ADD COLUMN last_used_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE videos ADD COLUMN hit_count INTEGER NOT NULL DEFAULT 0;
+UPDATE videos SET created_at = unixepoch(), last_used_at = unixepoch();
+CREATE INDEX IF NOT EXISTS videos_last_used_at ON videos (last_used_at);
+
+ALTER TABLE media_groups ADD COLUMN file_unique_id TEXT;
+ALTER TABLE media_groups ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE media_groups ADD COLUMN last_used_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE media_groups ADD COLUMN hit_count INTEGER NOT NULL DEFAULT 0;
+UPDATE media_groups SET created_at = unixepoch(), last_used_at = unixepoch();
+CREATE INDEX IF NOT EXISTS media_groups_last_used_at ON media_groups (last_used_at);
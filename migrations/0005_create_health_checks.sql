@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS health_checks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    resolver TEXT NOT NULL,
+    url TEXT NOT NULL,
+    success INTEGER NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    error TEXT,
+    checked_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS health_checks_checked_at ON health_checks (checked_at);
+
+CREATE TABLE IF NOT EXISTS resolver_status (
+    resolver TEXT PRIMARY KEY,
+    healthy INTEGER NOT NULL,
+    changed_at INTEGER NOT NULL
+);
@@ -0,0 +1,245 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use worker::Env;
+
+use crate::url_resolver::{
+    chain::{Resolver, ResolverOptions},
+    format::FormatPolicy,
+    Platform,
+};
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Config {
+    pub api_path: String,
+    pub webhook_secret: Option<String>,
+    /// Token for admin endpoints, they are disabled when absent.
+    pub admin_token: Option<String>,
+    /// Base URL of the worker, `/api/update` is appended to it. Falls back to
+    /// the origin of `/api/webhook` request when absent.
+    pub webhook_base_url: Option<String>,
+    /// Username without `@`. Fetched via `getMe` when absent.
+    pub bot_username: Option<String>,
+    pub db_binding: String,
+    pub allowed_platforms: Vec<Platform>,
+    /// Ordered fallback chain of resolvers for every platform.
+    pub resolvers: HashMap<Platform, Vec<Resolver>>,
+    pub resolver_timeout_ms: u64,
+    /// Longer YouTube videos are rejected, in seconds.
+    pub youtube_max_duration: u32,
+    /// Maximum number of links of a single message processed at the same time.
+    pub max_concurrent_links: usize,
+    /// Further links of a single message are skipped.
+    pub max_links_per_message: usize,
+    /// Cached media not used for this many days is ignored and purged.
+    pub cache_ttl_days: u64,
+    /// Links every resolver of their platform is checked against on schedule.
+    pub health_check_urls: Vec<String>,
+    /// Chat notified when a resolver starts failing or recovers.
+    pub admin_chat_id: Option<i64>,
+}
+
+impl Config {
+    pub fn new(env: &Env) -> Result<Self> {
+        let api_path = env
+            .secret("BOT_TOKEN")
+            .map(|token| format!("https://api.telegram.org/bot{}", token.to_string()))
+            .map_err(|_| anyhow!("`BOT_TOKEN` secret is not set"))?;
+
+        let config = Self {
+            api_path,
+            webhook_secret: get_string(env, "WEBHOOK_SECRET"),
+            admin_token: get_string(env, "ADMIN_TOKEN"),
+            webhook_base_url: get_string(env, "WEBHOOK_BASE_URL"),
+            bot_username: get_string(env, "BOT_USERNAME")
+                .map(|username| username.trim_start_matches('@').to_string()),
+            db_binding: get_string(env, "DB_BINDING").unwrap_or("DB".to_string()),
+            allowed_platforms: match get_string(env, "ALLOWED_PLATFORMS") {
+                Some(platforms) => platforms
+                    .split(',')
+                    .map(|it| it.trim().parse())
+                    .collect::<Result<_>>()?,
+                None => Platform::ALL.to_vec(),
+            },
+            resolvers: Platform::ALL
+                .iter()
+                .map(|platform| {
+                    let name = format!("{}_RESOLVERS", platform.name().to_uppercase());
+                    let chain = match get_string(env, &name) {
+                        Some(chain) => chain
+                            .split(',')
+                            .map(|it| it.trim().parse())
+                            .collect::<Result<_>>()?,
+                        None => Resolver::default_chain(platform),
+                    };
+                    Ok((*platform, chain))
+                })
+                .collect::<Result<_>>()?,
+            resolver_timeout_ms: get_string(env, "RESOLVER_TIMEOUT_MS")
+                .map(|it| it.parse())
+                .transpose()
+                .map_err(|err| anyhow!("`RESOLVER_TIMEOUT_MS` is invalid: {err}"))?
+                .unwrap_or(15_000),
+            youtube_max_duration: get_string(env, "YOUTUBE_MAX_DURATION")
+                .map(|it| it.parse())
+                .transpose()
+                .map_err(|err| anyhow!("`YOUTUBE_MAX_DURATION` is invalid: {err}"))?
+                .unwrap_or(600),
+            max_concurrent_links: get_string(env, "MAX_CONCURRENT_LINKS")
+                .map(|it| it.parse())
+                .transpose()
+                .map_err(|err| anyhow!("`MAX_CONCURRENT_LINKS` is invalid: {err}"))?
+                .unwrap_or(3),
+            max_links_per_message: get_string(env, "MAX_LINKS_PER_MESSAGE")
+                .map(|it| it.parse())
+                .transpose()
+                .map_err(|err| anyhow!("`MAX_LINKS_PER_MESSAGE` is invalid: {err}"))?
+                .unwrap_or(10),
+            cache_ttl_days: get_string(env, "CACHE_TTL_DAYS")
+                .map(|it| it.parse())
+                .transpose()
+                .map_err(|err| anyhow!("`CACHE_TTL_DAYS` is invalid: {err}"))?
+                .unwrap_or(30),
+            health_check_urls: match get_string(env, "HEALTH_CHECK_URLS") {
+                Some(urls) => urls.split(',').map(|it| it.trim().to_string()).collect(),
+                None => vec![],
+            },
+            admin_chat_id: get_string(env, "ADMIN_CHAT_ID")
+                .map(|it| it.parse())
+                .transpose()
+                .map_err(|err| anyhow!("`ADMIN_CHAT_ID` is invalid: {err}"))?,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(webhook_secret) = &self.webhook_secret {
+            if webhook_secret.is_empty()
+                || webhook_secret.len() > 256
+                || !webhook_secret
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+            {
+                bail!(
+                    "`WEBHOOK_SECRET` must be 1-256 characters of `A-Z`, `a-z`, `0-9`, `_` and `-`"
+                );
+            }
+        }
+
+        if let Some(webhook_base_url) = &self.webhook_base_url {
+            let url = Url::parse(webhook_base_url)
+                .map_err(|err| anyhow!("`WEBHOOK_BASE_URL` is invalid: {err}"))?;
+            if url.scheme() != "https" {
+                bail!("`WEBHOOK_BASE_URL` must use `https` scheme");
+            }
+        }
+
+        if let Some(bot_username) = &self.bot_username {
+            if bot_username.is_empty()
+                || !bot_username
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+            {
+                bail!("`BOT_USERNAME` is invalid: {bot_username}");
+            }
+        }
+
+        if self.db_binding.is_empty() {
+            bail!("`DB_BINDING` must not be empty");
+        }
+
+        if self.allowed_platforms.is_empty() {
+            bail!("`ALLOWED_PLATFORMS` must contain at least one platform");
+        }
+
+        for (platform, chain) in &self.resolvers {
+            if chain.is_empty() {
+                bail!("Resolver chain for {} must not be empty", platform.name());
+            }
+            if let Some(resolver) = chain.iter().find(|it| !it.supports(platform)) {
+                bail!(
+                    "`{}` resolver does not support {}",
+                    resolver.name(),
+                    platform.name()
+                );
+            }
+        }
+
+        if self.resolver_timeout_ms == 0 {
+            bail!("`RESOLVER_TIMEOUT_MS` must be greater than zero");
+        }
+
+        if self.youtube_max_duration == 0 {
+            bail!("`YOUTUBE_MAX_DURATION` must be greater than zero");
+        }
+
+        if self.max_concurrent_links == 0 {
+            bail!("`MAX_CONCURRENT_LINKS` must be greater than zero");
+        }
+
+        if self.max_links_per_message == 0 {
+            bail!("`MAX_LINKS_PER_MESSAGE` must be greater than zero");
+        }
+
+        if self.cache_ttl_days == 0 {
+            bail!("`CACHE_TTL_DAYS` must be greater than zero");
+        }
+
+        for url in &self.health_check_urls {
+            Url::parse(url)
+                .map_err(|err| anyhow!(err))
+                .and_then(|url| Platform::new(&url))
+                .map_err(|err| anyhow!("`HEALTH_CHECK_URLS` has {url}: {err}"))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn resolver_chain(&self, platform: &Platform) -> &[Resolver] {
+        self.resolvers
+            .get(platform)
+            .map(|chain| chain.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn resolver_timeout(&self) -> Duration {
+        Duration::from_millis(self.resolver_timeout_ms)
+    }
+
+    pub fn resolver_options(&self, policy: FormatPolicy) -> ResolverOptions {
+        ResolverOptions {
+            policy,
+            max_duration: self.youtube_max_duration,
+            headerless: false,
+        }
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_days * 24 * 60 * 60)
+    }
+
+    pub fn is_platform_allowed(&self, platform: &Platform) -> bool {
+        self.allowed_platforms.contains(platform)
+    }
+
+    /// Builds the `/api/update` URL from `WEBHOOK_BASE_URL` or `request_url`.
+    pub fn webhook_url(&self, request_url: &Url) -> Result<Url> {
+        let base_url = match &self.webhook_base_url {
+            Some(base_url) => base_url.trim_end_matches('/').to_string(),
+            None => request_url.origin().ascii_serialization(),
+        };
+        Ok(Url::parse(&format!("{base_url}/api/update"))?)
+    }
+}
+
+fn get_string(env: &Env, name: &str) -> Option<String> {
+    env.secret(name)
+        .or_else(|_| env.var(name))
+        .map(|value| value.to_string())
+        .ok()
+        .filter(|value| !value.is_empty())
+}
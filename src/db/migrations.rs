@@ -0,0 +1,46 @@
+/// Versioned schema change applied once and recorded in `schema_migrations` table.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    /// Statements separated by `;`.
+    pub sql: &'static str,
+}
+
+/// Every migration in the order of versions. Applied migrations must never
+/// be changed, add a new one instead.
+pub const MIGRATIONS: [Migration; 5] = [
+    Migration {
+        version: 1,
+        name: "create_videos",
+        sql: include_str!("../../migrations/0001_create_videos.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_media_groups",
+        sql: include_str!("../../migrations/0002_create_media_groups.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "create_chat_settings",
+        sql: include_str!("../../migrations/0003_create_chat_settings.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "add_cache_metadata",
+        sql: include_str!("../../migrations/0004_add_cache_metadata.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "create_health_checks",
+        sql: include_str!("../../migrations/0005_create_health_checks.sql"),
+    },
+];
+
+impl Migration {
+    pub fn statements(&self) -> impl Iterator<Item = &'static str> {
+        self.sql
+            .split(';')
+            .map(str::trim)
+            .filter(|it| !it.is_empty())
+    }
+}
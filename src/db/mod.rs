@@ -0,0 +1,506 @@
+mod migrations;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::time::Duration;
+
+use worker::{console_error, wasm_bindgen::JsValue, D1Database, D1PreparedStatement, Env};
+
+use crate::{
+    telegram::MediaFile,
+    url_resolver::{format::FormatPolicy, MediaKind, Platform},
+};
+use migrations::MIGRATIONS;
+
+pub struct Db {
+    db: D1Database,
+}
+
+/// How the link and the author are attached to sent media.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionStyle {
+    /// Link and author.
+    Full,
+    Link,
+    None,
+}
+
+impl CaptionStyle {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Link => "link",
+            Self::None => "none",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "link" => Self::Link,
+            "none" => Self::None,
+            _ => Self::Full,
+        }
+    }
+}
+
+/// Preferred quality for services that offer several formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    High,
+    Low,
+}
+
+impl Quality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Low => "low",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "low" => Self::Low,
+            _ => Self::High,
+        }
+    }
+
+    pub fn format_policy(&self) -> FormatPolicy {
+        match self {
+            Self::High => FormatPolicy::HIGH,
+            Self::Low => FormatPolicy::LOW,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatSettings {
+    /// Answer every message with a supported link in groups, not only mentions.
+    pub auto_download: bool,
+    pub caption_style: CaptionStyle,
+    pub quality: Quality,
+    /// Delete the message with the link after its media is sent.
+    pub delete_original: bool,
+    /// `None` allows every platform allowed by the config.
+    pub allowed_platforms: Option<Vec<Platform>>,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            auto_download: false,
+            caption_style: CaptionStyle::Full,
+            quality: Quality::High,
+            delete_original: false,
+            allowed_platforms: None,
+        }
+    }
+}
+
+impl ChatSettings {
+    pub fn is_platform_allowed(&self, platform: &Platform) -> bool {
+        self.allowed_platforms
+            .as_ref()
+            .is_none_or(|platforms| platforms.contains(platform))
+    }
+}
+
+/// Row of `chat_settings` table. SQLite has no booleans, so flags are `0` or `1`.
+#[derive(Debug, Deserialize)]
+struct ChatSettingsRow {
+    auto_download: i64,
+    caption_style: String,
+    quality: String,
+    delete_original: i64,
+    /// Comma-separated platforms.
+    allowed_platforms: Option<String>,
+}
+
+impl From<ChatSettingsRow> for ChatSettings {
+    fn from(row: ChatSettingsRow) -> Self {
+        Self {
+            auto_download: row.auto_download != 0,
+            caption_style: CaptionStyle::parse(&row.caption_style),
+            quality: Quality::parse(&row.quality),
+            delete_original: row.delete_original != 0,
+            allowed_platforms: row.allowed_platforms.map(|platforms| {
+                platforms
+                    .split(',')
+                    .filter_map(|it| it.parse().ok())
+                    .collect()
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaMigration {
+    version: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolverStatus {
+    healthy: i64,
+}
+
+/// Result of a single resolver run against a canary URL.
+#[derive(Debug)]
+pub struct HealthCheck<'a> {
+    pub resolver: &'a str,
+    pub url: &'a str,
+    pub latency_ms: u64,
+    /// `None` on success.
+    pub error: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CachedMedia {
+    pub kind: MediaKind,
+    pub file_id: String,
+}
+
+impl Db {
+    pub fn new(env: &Env, binding: &str) -> Option<Self> {
+        env.d1(binding).map_or_else(
+            |err| {
+                console_error!("{err}");
+                None
+            },
+            |db| Some(Self { db }),
+        )
+    }
+
+    /// Applies pending migrations, each one in a separate transaction.
+    /// Returns versions of applied migrations.
+    pub async fn migrate(&self) -> Result<Vec<u32>> {
+        self.db
+            .prepare(
+                "CREATE TABLE IF NOT EXISTS schema_migrations (\
+                 version INTEGER PRIMARY KEY, \
+                 name TEXT NOT NULL, \
+                 applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP)",
+            )
+            .run()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+
+        let applied: Vec<_> = self
+            .db
+            .prepare("SELECT version FROM schema_migrations")
+            .all()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .results::<SchemaMigration>()
+            .map_err(|err| anyhow!(err.to_string()))?
+            .into_iter()
+            .map(|it| it.version)
+            .collect();
+
+        let mut versions = vec![];
+        for migration in MIGRATIONS
+            .iter()
+            .filter(|it| !applied.contains(&it.version))
+        {
+            let mut statements: Vec<_> = migration
+                .statements()
+                .map(|sql| self.db.prepare(sql))
+                .collect();
+            statements.push(
+                self.db
+                    .prepare("INSERT INTO schema_migrations (version, name) VALUES (?1, ?2)")
+                    .bind(&[migration.version.into(), migration.name.into()])
+                    .map_err(|err| anyhow!(err.to_string()))?,
+            );
+
+            self.db.batch(statements).await.map_err(|err| {
+                anyhow!(
+                    "Migration {} `{}` failed: {err}",
+                    migration.version,
+                    migration.name
+                )
+            })?;
+            versions.push(migration.version);
+        }
+
+        Ok(versions)
+    }
+
+    pub async fn get_video(&self, url: &str, ttl: Duration) -> Result<Option<CachedMedia>> {
+        self.db
+            .prepare(
+                "SELECT kind, file_id FROM videos \
+                 WHERE url = ?1 AND last_used_at > unixepoch() - ?2",
+            )
+            .bind(&[url.into(), (ttl.as_secs() as f64).into()])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .first::<CachedMedia>(None)
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+    }
+
+    pub async fn get_media_group(&self, url: &str, ttl: Duration) -> Result<Vec<CachedMedia>> {
+        self.db
+            .prepare(
+                "SELECT kind, file_id FROM media_groups \
+                 WHERE url = ?1 AND last_used_at > unixepoch() - ?2 ORDER BY position",
+            )
+            .bind(&[url.into(), (ttl.as_secs() as f64).into()])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .all()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .results::<CachedMedia>()
+            .map_err(|err| anyhow!(err.to_string()))
+    }
+
+    /// Returns cached media for `url` used within `ttl`: a single item from
+    /// `videos` table or every item of an album from `media_groups` table.
+    pub async fn get_media(&self, url: &str, ttl: Duration) -> Result<Vec<CachedMedia>> {
+        if let Some(video) = self.get_video(url, ttl).await? {
+            return Ok(vec![video]);
+        }
+
+        self.get_media_group(url, ttl).await
+    }
+
+    /// Replaces cached media of `url`. A single item is stored into `videos`
+    /// table and albums into `media_groups` table.
+    pub async fn insert_media(&self, url: &str, media: &[MediaFile<'_>]) -> Result<bool> {
+        let mut statements = self.delete_media_statements(url)?;
+        if let [file] = media {
+            statements.push(
+                self.db
+                    .prepare(
+                        "INSERT INTO videos \
+                         (url, kind, file_id, file_unique_id, created_at, last_used_at, hit_count) \
+                         VALUES (?1, ?2, ?3, ?4, unixepoch(), unixepoch(), 0)",
+                    )
+                    .bind(&[
+                        url.into(),
+                        file.kind.as_str().into(),
+                        file.file_id.into(),
+                        file.file_unique_id.into(),
+                    ])
+                    .map_err(|err| anyhow!(err.to_string()))?,
+            );
+        } else {
+            for (position, file) in media.iter().enumerate() {
+                statements.push(
+                    self.db
+                        .prepare(
+                            "INSERT INTO media_groups \
+                             (url, position, kind, file_id, file_unique_id, created_at, last_used_at, hit_count) \
+                             VALUES (?1, ?2, ?3, ?4, ?5, unixepoch(), unixepoch(), 0)",
+                        )
+                        .bind(&[
+                            url.into(),
+                            (position as u32).into(),
+                            file.kind.as_str().into(),
+                            file.file_id.into(),
+                            file.file_unique_id.into(),
+                        ])
+                        .map_err(|err| anyhow!(err.to_string()))?,
+                );
+            }
+        }
+
+        self.db
+            .batch(statements)
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|results| results.iter().all(|it| it.success()))
+    }
+
+    /// Records a cache hit of `url`.
+    pub async fn touch_media(&self, url: &str) -> Result<bool> {
+        let statements = ["videos", "media_groups"]
+            .iter()
+            .map(|table| {
+                self.db
+                    .prepare(format!(
+                        "UPDATE {table} SET last_used_at = unixepoch(), hit_count = hit_count + 1 \
+                         WHERE url = ?1"
+                    ))
+                    .bind(&[url.into()])
+                    .map_err(|err| anyhow!(err.to_string()))
+            })
+            .collect::<Result<_>>()?;
+
+        self.db
+            .batch(statements)
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|results| results.iter().all(|it| it.success()))
+    }
+
+    /// Invalidates cached media of `url`.
+    pub async fn delete_media(&self, url: &str) -> Result<bool> {
+        self.db
+            .batch(self.delete_media_statements(url)?)
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|results| results.iter().all(|it| it.success()))
+    }
+
+    /// Deletes cached media not used within `ttl`.
+    pub async fn purge_media(&self, ttl: Duration) -> Result<bool> {
+        let statements = ["videos", "media_groups"]
+            .iter()
+            .map(|table| {
+                self.db
+                    .prepare(format!(
+                        "DELETE FROM {table} WHERE last_used_at <= unixepoch() - ?1"
+                    ))
+                    .bind(&[(ttl.as_secs() as f64).into()])
+                    .map_err(|err| anyhow!(err.to_string()))
+            })
+            .collect::<Result<_>>()?;
+
+        self.db
+            .batch(statements)
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|results| results.iter().all(|it| it.success()))
+    }
+
+    fn delete_media_statements(&self, url: &str) -> Result<Vec<D1PreparedStatement>> {
+        ["videos", "media_groups"]
+            .iter()
+            .map(|table| {
+                self.db
+                    .prepare(format!("DELETE FROM {table} WHERE url = ?1"))
+                    .bind(&[url.into()])
+                    .map_err(|err| anyhow!(err.to_string()))
+            })
+            .collect()
+    }
+
+    /// Returns settings of `chat_id`, defaults when the chat has none.
+    pub async fn get_chat_settings(&self, chat_id: i64) -> Result<ChatSettings> {
+        self.db
+            .prepare(
+                "SELECT auto_download, caption_style, quality, delete_original, allowed_platforms \
+                 FROM chat_settings WHERE chat_id = ?1",
+            )
+            .bind(&[(chat_id as f64).into()])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .first::<ChatSettingsRow>(None)
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|row| row.map(ChatSettings::from).unwrap_or_default())
+    }
+
+    pub async fn upsert_chat_settings(
+        &self,
+        chat_id: i64,
+        settings: &ChatSettings,
+    ) -> Result<bool> {
+        let allowed_platforms = match &settings.allowed_platforms {
+            Some(platforms) => platforms
+                .iter()
+                .map(|platform| platform.name().to_lowercase())
+                .collect::<Vec<_>>()
+                .join(",")
+                .into(),
+            None => JsValue::NULL,
+        };
+
+        self.db
+            .prepare(
+                "INSERT INTO chat_settings \
+                 (chat_id, auto_download, caption_style, quality, delete_original, allowed_platforms) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6) \
+                 ON CONFLICT (chat_id) DO UPDATE SET \
+                 auto_download = excluded.auto_download, \
+                 caption_style = excluded.caption_style, \
+                 quality = excluded.quality, \
+                 delete_original = excluded.delete_original, \
+                 allowed_platforms = excluded.allowed_platforms",
+            )
+            .bind(&[
+                (chat_id as f64).into(),
+                settings.auto_download.into(),
+                settings.caption_style.as_str().into(),
+                settings.quality.as_str().into(),
+                settings.delete_original.into(),
+                allowed_platforms,
+            ])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .run()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|it| it.success())
+    }
+
+    /// Moves settings of a group upgraded to a supergroup.
+    pub async fn migrate_chat(&self, chat_id: i64, migrate_to_chat_id: i64) -> Result<bool> {
+        self.db
+            .prepare("UPDATE OR REPLACE chat_settings SET chat_id = ?2 WHERE chat_id = ?1")
+            .bind(&[(chat_id as f64).into(), (migrate_to_chat_id as f64).into()])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .run()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|it| it.success())
+    }
+
+    pub async fn insert_health_check(&self, check: &HealthCheck<'_>) -> Result<bool> {
+        self.db
+            .prepare(
+                "INSERT INTO health_checks (resolver, url, success, latency_ms, error, checked_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, unixepoch())",
+            )
+            .bind(&[
+                check.resolver.into(),
+                check.url.into(),
+                check.error.is_none().into(),
+                (check.latency_ms as f64).into(),
+                check.error.map_or(JsValue::NULL, |it| it.into()),
+            ])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .run()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|it| it.success())
+    }
+
+    /// Deletes health checks older than `ttl`.
+    pub async fn purge_health_checks(&self, ttl: Duration) -> Result<bool> {
+        self.db
+            .prepare("DELETE FROM health_checks WHERE checked_at <= unixepoch() - ?1")
+            .bind(&[(ttl.as_secs() as f64).into()])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .run()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|it| it.success())
+    }
+
+    /// Returns the last known health of `resolver`, `None` before the first check.
+    pub async fn get_resolver_health(&self, resolver: &str) -> Result<Option<bool>> {
+        self.db
+            .prepare("SELECT healthy FROM resolver_status WHERE resolver = ?1")
+            .bind(&[resolver.into()])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .first::<ResolverStatus>(None)
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|it| it.map(|it| it.healthy != 0))
+    }
+
+    pub async fn set_resolver_health(&self, resolver: &str, healthy: bool) -> Result<bool> {
+        self.db
+            .prepare(
+                "INSERT INTO resolver_status (resolver, healthy, changed_at) \
+                 VALUES (?1, ?2, unixepoch()) \
+                 ON CONFLICT (resolver) DO UPDATE SET \
+                 healthy = excluded.healthy, changed_at = excluded.changed_at",
+            )
+            .bind(&[resolver.into(), healthy.into()])
+            .map_err(|err| anyhow!(err.to_string()))?
+            .run()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))
+            .map(|it| it.success())
+    }
+}
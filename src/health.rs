@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use futures::future::join_all;
+use url::Url;
+use worker::{console_error, console_log, Date};
+
+use crate::{
+    config::Config,
+    db::{Db, HealthCheck},
+    telegram::{SendMessage, Telegram},
+    url_resolver::{
+        chain::{resolve_with_timeout, Resolver},
+        format::FormatPolicy,
+        Platform,
+    },
+};
+
+/// How long results of health checks are kept.
+const HEALTH_CHECK_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Runs every resolver against `HEALTH_CHECK_URLS` of its platform, records
+/// results and notifies `ADMIN_CHAT_ID` when a resolver starts failing or
+/// recovers.
+pub async fn run_health_checks(
+    config: &Config,
+    db: &Db,
+    http_client: &reqwest::Client,
+    tg_client: &Telegram<'_>,
+) {
+    join_all(
+        Resolver::ALL
+            .iter()
+            .map(|resolver| check_resolver(config, db, http_client, tg_client, resolver)),
+    )
+    .await;
+
+    if let Err(err) = db.purge_health_checks(HEALTH_CHECK_RETENTION).await {
+        console_error!("`db.purge_health_checks` error: {err}");
+    }
+}
+
+async fn check_resolver(
+    config: &Config,
+    db: &Db,
+    http_client: &reqwest::Client,
+    tg_client: &Telegram<'_>,
+    resolver: &Resolver,
+) {
+    let urls: Vec<_> = config
+        .health_check_urls
+        .iter()
+        .filter(|url| {
+            Url::parse(url)
+                .ok()
+                .and_then(|url| Platform::new(&url).ok())
+                .is_some_and(|it| resolver.supports(&it))
+        })
+        .collect();
+    if urls.is_empty() {
+        return;
+    }
+
+    let mut first_error = None;
+    for url in urls {
+        let started_at = Date::now().as_millis();
+        let error = match resolve_with_timeout(
+            http_client,
+            resolver,
+            url,
+            config.resolver_timeout(),
+            &config.resolver_options(FormatPolicy::default()),
+        )
+        .await
+        {
+            Ok(media) if media.is_empty() => Some("No media".to_string()),
+            Ok(_) => None,
+            Err(err) => Some(err.to_string()),
+        };
+        let latency_ms = Date::now().as_millis() - started_at;
+
+        let check = HealthCheck {
+            resolver: resolver.name(),
+            url,
+            latency_ms,
+            error: error.as_deref(),
+        };
+        if let Err(err) = db.insert_health_check(&check).await {
+            console_error!("`db.insert_health_check` error: {err}");
+        }
+
+        if let (None, Some(error)) = (&first_error, error) {
+            first_error = Some(format!("{url}\n{error}"));
+        }
+    }
+
+    let is_healthy = first_error.is_none();
+    console_log!(
+        "`{}` health check {}",
+        resolver.name(),
+        if is_healthy { "passed" } else { "failed" }
+    );
+
+    let was_healthy = match db.get_resolver_health(resolver.name()).await {
+        Ok(was_healthy) => was_healthy,
+        Err(err) => {
+            console_error!("`db.get_resolver_health` error: {err}");
+            return;
+        }
+    };
+    if was_healthy == Some(is_healthy) {
+        return;
+    }
+
+    if let Err(err) = db.set_resolver_health(resolver.name(), is_healthy).await {
+        console_error!("`db.set_resolver_health` error: {err}");
+    }
+
+    // Nothing has changed for a resolver that is healthy since the first check.
+    if was_healthy.is_none() && is_healthy {
+        return;
+    }
+
+    let admin_chat_id = match config.admin_chat_id {
+        Some(admin_chat_id) => admin_chat_id,
+        None => return,
+    };
+    let platforms = resolver
+        .platforms()
+        .iter()
+        .map(|it| it.name())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let text = match first_error {
+        Some(error) => format!(
+            "⚠️ `{}` resolver of {platforms} started failing:\n\n{error}",
+            resolver.name()
+        ),
+        None => format!(
+            "✅ `{}` resolver of {platforms} recovered.",
+            resolver.name()
+        ),
+    };
+    if let Err(err) = tg_client
+        .send_message(&SendMessage {
+            chat_id: admin_chat_id,
+            text,
+            reply_to_message_id: None,
+            link_preview_options: None,
+            reply_markup: None,
+        })
+        .await
+    {
+        console_error!("{err}");
+    }
+}
@@ -1,43 +1,113 @@
-use db::Db;
+use config::Config;
+use db::{CaptionStyle, ChatSettings, Db, Quality};
+use futures::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::sync::{Once, OnceLock};
 use url_resolver::{
-    shorts::ShortsUrlResolver, snap::SnapUrlResolver, twitter::TwitterUrlResolver, Platform,
-    ResolveUrl,
+    canonical::{canonicalize, CanonicalUrl},
+    chain::{resolve_with_fallback, ChainError, Resolver, ResolverOptions},
+    http::HttpClient,
+    snap::DecodeError,
+    MediaKind, Platform, ResolvedMedia,
 };
 use worker::*;
 
+mod config;
 mod db;
+mod health;
 mod telegram;
 mod url_resolver;
 
-use telegram::{DeleteMessage, EditMessageText, LinkPreviewOptions, Telegram};
+use telegram::{
+    AnswerCallbackQuery, AnswerInlineQuery, DeleteMessage, EditMessageReplyMarkup, EditMessageText,
+    GetChatMember, InlineKeyboardButton, InlineKeyboardMarkup, InlineQueryResult,
+    InlineQueryResultCachedPhoto, InlineQueryResultCachedVideo, InlineQueryResultPhoto,
+    InlineQueryResultType, InlineQueryResultVideo, InputMedia, LinkPreviewOptions, Telegram,
+};
 
 #[derive(Deserialize, Serialize)]
 struct RouterData {
-    api_path: String,
+    config: Config,
 }
 
 #[event(fetch)]
 pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
     console_error_panic_hook::set_once();
 
-    let api_path = env
-        .secret("BOT_TOKEN")
-        .map(|token| format!("https://api.telegram.org/bot{}", token.to_string()))
-        .unwrap();
+    let config = match Config::new(&env) {
+        Ok(config) => config,
+        Err(err) => {
+            console_error!("Invalid configuration: {err}");
+            return Response::error("Invalid configuration", 500);
+        }
+    };
+
+    if config.webhook_secret.is_none() {
+        // Logged once per isolate rather than for every update.
+        static MISSING_SECRET: Once = Once::new();
+        MISSING_SECRET.call_once(|| {
+            console_error!("`WEBHOOK_SECRET` is not set, updates are accepted from anyone")
+        });
+    }
 
-    Router::with_data(RouterData { api_path })
+    Router::with_data(RouterData { config })
         .get_async("/api/webhook", setup_webhook)
         .post_async("/api/update", process_update)
+        .post_async("/api/migrate", migrate)
         .run(req, env)
         .await
 }
 
-async fn setup_webhook(_req: Request, ctx: RouteContext<RouterData>) -> Result<Response> {
+/// Purges stale cache entries and runs resolver health checks.
+#[event(scheduled)]
+pub async fn scheduled(_event: ScheduledEvent, env: Env, _ctx: ScheduleContext) {
+    console_error_panic_hook::set_once();
+
+    let config = match Config::new(&env) {
+        Ok(config) => config,
+        Err(err) => {
+            console_error!("Invalid configuration: {err}");
+            return;
+        }
+    };
+
+    let db = match Db::new(&env, &config.db_binding) {
+        Some(db) => db,
+        None => return,
+    };
+
+    if let Err(err) = db.purge_media(config.cache_ttl()).await {
+        console_error!("`db.purge_media` error: {err}");
+    }
+
     let http_client = reqwest::Client::new();
-    let tg_client = Telegram::new(&http_client, &ctx.data.api_path);
+    let tg_client = Telegram::new(&http_client, &config.api_path);
+    health::run_health_checks(&config, &db, &http_client, &tg_client).await;
+}
+
+async fn setup_webhook(req: Request, ctx: RouteContext<RouterData>) -> Result<Response> {
+    let config = &ctx.data.config;
+    let http_client = reqwest::Client::new();
+    let tg_client = Telegram::new(&http_client, &config.api_path);
+
+    // Without a secret token anyone who knows the URL can send updates.
+    let secret_token = match config.webhook_secret.as_deref() {
+        Some(secret_token) => secret_token,
+        None => {
+            console_error!("`WEBHOOK_SECRET` is required to set up the webhook");
+            return Response::error("`WEBHOOK_SECRET` is not set", 500);
+        }
+    };
+    let webhook_url = match config.webhook_url(&req.url()?) {
+        Ok(url) => url,
+        Err(err) => {
+            console_error!("{err}");
+            return Response::error("Invalid webhook URL", 500);
+        }
+    };
     let webhook = telegram::SetWebhook {
-        url: "https://snaptik-bot.ty3uk.workers.dev/api/update",
+        url: webhook_url.as_str(),
+        secret_token,
     };
 
     match tg_client.set_webhook(&webhook).await {
@@ -48,29 +118,91 @@ async fn setup_webhook(_req: Request, ctx: RouteContext<RouterData>) -> Result<R
     Response::ok("Success")
 }
 
+/// Applies pending database migrations. Requires `Authorization: Bearer <ADMIN_TOKEN>` header.
+async fn migrate(req: Request, ctx: RouteContext<RouterData>) -> Result<Response> {
+    let config = &ctx.data.config;
+    let admin_token = match &config.admin_token {
+        Some(admin_token) => admin_token.as_str(),
+        None => return Response::error("Not Found", 404),
+    };
+    let authorization = req.headers().get("Authorization")?;
+    let token = authorization
+        .as_deref()
+        .and_then(|it| it.strip_prefix("Bearer "));
+    if !telegram::verify_secret_token(Some(admin_token), token) {
+        console_error!("Invalid admin token");
+        return Response::error("Unauthorized", 401);
+    }
+
+    let db = match Db::new(&ctx.env, &config.db_binding) {
+        Some(db) => db,
+        None => return Response::error("Database is not available", 500),
+    };
+
+    match db.migrate().await {
+        Ok(versions) => Response::from_json(&versions),
+        Err(err) => {
+            console_error!("{err}");
+            Response::error(err.to_string(), 500)
+        }
+    }
+}
+
 async fn process_update(mut req: Request, ctx: RouteContext<RouterData>) -> Result<Response> {
-    let db = Db::new(&ctx.env);
+    let secret_token = req.headers().get(telegram::SECRET_TOKEN_HEADER)?;
+    let config = &ctx.data.config;
+    if !telegram::verify_secret_token(config.webhook_secret.as_deref(), secret_token.as_deref()) {
+        console_error!("Invalid webhook secret token");
+        return Response::error("Unauthorized", 401);
+    }
+
+    let db = Db::new(&ctx.env, &config.db_binding);
     let http_client = reqwest::Client::new();
-    let tg_client = Telegram::new(&http_client, &ctx.data.api_path);
+    let tg_client = Telegram::new(&http_client, &config.api_path);
 
     let update = match req.json::<telegram::Update>().await {
-        Ok(data) => match data.message {
-            Some(data) => data,
-            None => return Response::ok(""),
-        },
+        Ok(data) => data,
         Err(err) => {
             console_error!("{}", err);
             return Response::ok("");
         }
     };
 
+    if let Some(inline_query) = update.inline_query {
+        return process_inline_query(inline_query, config, &db, &http_client, &tg_client).await;
+    }
+
+    let bot = Bot {
+        config,
+        db: &db,
+        http_client: &http_client,
+        tg_client: &tg_client,
+    };
+
+    if let Some(callback_query) = update.callback_query {
+        process_callback_query(&bot, callback_query).await;
+        return Response::ok("");
+    }
+
+    let update = match update.message {
+        Some(data) => data,
+        None => return Response::ok(""),
+    };
+
     let chat = match &update.chat {
         Some(chat) => chat,
         None => return Response::ok(""),
     };
 
-    let mut message_text = match update.text {
-        Some(text) => text,
+    if let (Some(db), Some(migrate_to_chat_id)) = (&db, update.migrate_to_chat_id) {
+        if let Err(err) = db.migrate_chat(chat.id, migrate_to_chat_id).await {
+            console_error!("`db.migrate_chat` error: {err}");
+        }
+        return Response::ok("");
+    }
+
+    let message_text = match &update.text {
+        Some(text) => text.as_str(),
         None => return Response::ok(""),
     };
 
@@ -78,28 +210,88 @@ async fn process_update(mut req: Request, ctx: RouteContext<RouterData>) -> Resu
         return Response::ok("");
     }
 
-    if chat.chat_type != telegram::ChatType::Private {
-        if message_text != "@SnapTikRsBot" {
+    let mut is_auto_download = false;
+    let mut settings = None;
+    let mut original_message_id = update.message_id;
+    let mut links = if chat.chat_type != telegram::ChatType::Private {
+        let bot_username = match get_bot_username(config, &tg_client).await {
+            Ok(username) => username,
+            Err(err) => {
+                console_error!("{err}");
+                return Response::ok("");
+            }
+        };
+
+        if let Some((command, args)) = parse_command(message_text, &bot_username) {
+            match command {
+                "autodownload" => process_auto_download_command(&bot, chat.id, &update, args).await,
+                "settings" => process_settings_command(&bot, chat, &update).await,
+                _ => (),
+            }
             return Response::ok("");
         }
 
-        if update.reply_to_message.is_none() {
+        if message_text == format!("@{bot_username}") {
+            match &update.reply_to_message {
+                Some(reply_to_message) => {
+                    original_message_id = reply_to_message.message_id;
+                    reply_to_message.links()
+                }
+                None => return Response::ok(""),
+            }
+        } else {
+            let links = update.links();
+            if links.is_empty() {
+                return Response::ok("");
+            }
+
+            let chat_settings = get_chat_settings(&db, chat.id).await;
+            if !chat_settings.auto_download {
+                return Response::ok("");
+            }
+
+            let links: Vec<_> = links
+                .into_iter()
+                .filter(|link| is_supported_link(config, &chat_settings, link))
+                .collect();
+            if links.is_empty() {
+                return Response::ok("");
+            }
+
+            is_auto_download = true;
+            settings = Some(chat_settings);
+            links
+        }
+    } else {
+        if message_text == "/settings" {
+            process_settings_command(&bot, chat, &update).await;
             return Response::ok("");
         }
 
-        let update = *update.reply_to_message.unwrap();
-        message_text = update.text.unwrap();
-    }
+        update.links()
+    };
 
     if message_text == "/start" {
+        let bot_username = match get_bot_username(config, &tg_client).await {
+            Ok(username) => username,
+            Err(err) => {
+                console_error!("{err}");
+                return Response::ok("");
+            }
+        };
+
         if let Err(err) = tg_client
             .send_message(&telegram::SendMessage {
                 chat_id: chat.id,
-                text: include_str!("../assets/start_message.txt").to_string(),
+                text: format!(
+                    include_str!("../assets/start_message.txt"),
+                    bot_username = bot_username
+                ),
                 reply_to_message_id: None,
                 link_preview_options: Some(LinkPreviewOptions {
                     is_disabled: Some(true),
                 }),
+                reply_markup: None,
             })
             .await
         {
@@ -109,183 +301,1225 @@ async fn process_update(mut req: Request, ctx: RouteContext<RouterData>) -> Resu
         return Response::ok("");
     }
 
+    let settings = match settings {
+        Some(settings) => settings,
+        None => get_chat_settings(&db, chat.id).await,
+    };
+
+    // Every link takes several subrequests, which are limited per request.
+    let skipped_links = links.len().saturating_sub(config.max_links_per_message);
+    links.truncate(config.max_links_per_message);
+
     let message_to_edit = match tg_client
         .send_message(&telegram::SendMessage {
             chat_id: chat.id,
             text: "⏱️  Processing...".to_string(),
             reply_to_message_id: update.message_id,
             link_preview_options: None,
+            reply_markup: None,
         })
         .await
     {
         Ok(message) => message,
+        Err(err) if err.is_bot_blocked() || err.is_chat_not_found() => {
+            console_log!("Cannot reply in chat {}: {err}", chat.id);
+            return Response::ok("");
+        }
         Err(err) => {
             console_error!("{err}");
             return Response::ok("");
         }
     };
 
-    let send_bad_url_message = || async {
+    let results: Vec<_> = stream::iter(&links)
+        .map(|link| process_link(&bot, &settings, chat.id, update.message_id, link))
+        .buffered(config.max_concurrent_links)
+        .collect()
+        .await;
+
+    let failures: Vec<_> = links
+        .iter()
+        .zip(&results)
+        .filter_map(|(link, result)| match result {
+            LinkResult::Failed(reason) => Some(format!("{link}\n{reason}")),
+            LinkResult::TooLarge(_) => Some(format!("{link}\n{TOO_LARGE_MESSAGE}")),
+            _ => None,
+        })
+        .collect();
+    let buttons: Vec<_> = results
+        .iter()
+        .filter_map(|it| match it {
+            LinkResult::TooLarge(buttons) => Some(buttons),
+            _ => None,
+        })
+        .flatten()
+        .map(|button| vec![button.clone()])
+        .collect();
+
+    let is_unsupported = results
+        .iter()
+        .all(|it| matches!(it, LinkResult::Unsupported));
+    let skipped_message = (skipped_links > 0)
+        .then(|| get_skipped_links_message(config.max_links_per_message, skipped_links));
+    let text = if is_unsupported && !is_auto_download {
+        Some(get_bad_url_message(config, &settings))
+    } else if failures.is_empty() {
+        None
+    } else if let [LinkResult::Failed(reason)] = results.as_slice() {
+        Some(reason.clone())
+    } else if let [LinkResult::TooLarge(_)] = results.as_slice() {
+        Some(TOO_LARGE_MESSAGE.to_string())
+    } else {
+        Some(format!(
+            "❌ Some links were not processed:\n\n{}",
+            failures.join("\n\n")
+        ))
+    };
+    let text = match (text, skipped_message) {
+        (Some(text), Some(skipped_message)) => Some(format!("{text}\n\n{skipped_message}")),
+        (text, skipped_message) => text.or(skipped_message),
+    };
+
+    if let Some(text) = text {
         if let Err(err) = tg_client
             .edit_message_text(&EditMessageText {
                 chat_id: chat.id,
                 message_id: message_to_edit.message_id,
-                text: "❌ Only TikTok, Instagram or Shorts links are accepted.".to_string(),
+                text,
+                reply_markup: (!buttons.is_empty()).then_some(InlineKeyboardMarkup {
+                    inline_keyboard: buttons,
+                }),
             })
             .await
         {
             console_error!("{err}");
         }
-    };
+    } else if let Err(err) = tg_client
+        .delete_message(&DeleteMessage {
+            chat_id: chat.id,
+            message_id: message_to_edit.message_id.unwrap(),
+        })
+        .await
+    {
+        console_error!("{err}");
+    }
 
-    let mut url = match Url::parse(&message_text) {
+    // The original message is kept while some of its links are not sent.
+    let is_sent = skipped_links == 0 && results.iter().all(|it| matches!(it, LinkResult::Sent));
+    if let (true, true, Some(message_id)) = (is_sent, settings.delete_original, original_message_id)
+    {
+        if let Err(err) = tg_client
+            .delete_message(&DeleteMessage {
+                chat_id: chat.id,
+                message_id,
+            })
+            .await
+        {
+            console_error!("{err}");
+        }
+    }
+
+    Response::ok("")
+}
+
+/// Dependencies shared by handlers of a single update.
+struct Bot<'a> {
+    config: &'a Config,
+    db: &'a Option<Db>,
+    http_client: &'a reqwest::Client,
+    tg_client: &'a Telegram<'a>,
+}
+
+enum LinkResult {
+    Sent,
+    Unsupported,
+    /// Contains a message for the user.
+    Failed(String),
+    /// Contains buttons with direct links to the media.
+    TooLarge(Vec<InlineKeyboardButton>),
+}
+
+const TOO_LARGE_MESSAGE: &str = "❌ Video is too large to send it.";
+
+/// Longer titles are cut, captions are limited to 1024 characters.
+const MAX_TITLE_LENGTH: usize = 200;
+
+/// Seconds Telegram caches inline results for by default.
+const INLINE_CACHE_TIME: i64 = 300;
+
+/// Resolves `link` and sends its media to `chat_id`.
+async fn process_link(
+    bot: &Bot<'_>,
+    settings: &ChatSettings,
+    chat_id: i64,
+    reply_to_message_id: Option<isize>,
+    link: &str,
+) -> LinkResult {
+    let Bot {
+        config,
+        db,
+        http_client,
+        tg_client,
+    } = bot;
+
+    let url = match parse_url(link) {
         Ok(url) => url,
-        Err(_) => {
-            send_bad_url_message().await;
-            return Response::ok("");
+        Err(_) => return LinkResult::Unsupported,
+    };
+
+    let CanonicalUrl { url, key } = match canonicalize(*http_client, url).await {
+        Ok(canonical_url) => canonical_url,
+        // Only expansion of short links fails, the link itself may be fine.
+        Err(err) => {
+            console_error!("{err}");
+            return LinkResult::Failed("❌ Cannot open the short link.".to_string());
         }
     };
 
-    let mut url_path = url.path().to_owned();
-    if !url_path.ends_with('/') {
-        url_path.push('/');
-        url.set_path(&url_path);
-    }
+    // Checked before the cache, so disallowed platforms are not served from it.
+    let platform = match Platform::new(&url) {
+        Ok(platform)
+            if config.is_platform_allowed(&platform) && settings.is_platform_allowed(&platform) =>
+        {
+            platform
+        }
+        Ok(platform) => {
+            console_error!("{platform:?} is not allowed: {url}");
+            return LinkResult::Unsupported;
+        }
+        Err(err) => {
+            console_error!("{err}: {url}");
+            return LinkResult::Unsupported;
+        }
+    };
 
-    if let Some(db) = &db {
-        match db.get_video(&message_text).await {
-            Ok(Some(video)) => {
-                if let Err(err) = tg_client
-                    .send_video(&telegram::SendVideo {
-                        chat_id: chat.id,
-                        video: video.file_id,
-                        reply_to_message_id: update.message_id,
-                        caption: Some(message_text.clone()),
-                    })
-                    .await
-                {
-                    console_error!("{err}");
-                }
+    let cache_key = get_cache_key(config, &platform, key.clone(), settings.quality);
 
-                if let Err(err) = tg_client
-                    .delete_message(&DeleteMessage {
-                        chat_id: chat.id,
-                        message_id: message_to_edit.message_id.unwrap(),
-                    })
+    if let Some(db) = db {
+        match db.get_media(&cache_key, config.cache_ttl()).await {
+            Ok(media) if !media.is_empty() => {
+                let media = media
+                    .into_iter()
+                    .map(|it| InputMedia::new(it.kind, it.file_id))
+                    .collect();
+                match tg_client
+                    .send_album(
+                        chat_id,
+                        reply_to_message_id,
+                        get_caption(link, &[], settings.caption_style),
+                        media,
+                    )
                     .await
                 {
-                    console_error!("{err}");
+                    Ok(_) => {
+                        if let Err(err) = db.touch_media(&cache_key).await {
+                            console_error!("`db.touch_media` error: {err}");
+                        }
+                        return LinkResult::Sent;
+                    }
+                    // Cached `file_id` may become invalid, the row is
+                    // overwritten after fresh resolution. Only `file_id`s are
+                    // sent, so a rejected URL is an unknown `file_id` too.
+                    Err(err) if err.is_invalid_file_id() || err.is_url_rejected() => {
+                        console_error!("Cannot send cached media of {cache_key}: {err}");
+                        if let Err(err) = db.delete_media(&cache_key).await {
+                            console_error!("`db.delete_media` error: {err}");
+                        }
+                    }
+                    // Other errors are not caused by the cached media, the
+                    // row stays valid.
+                    Err(err) => {
+                        console_error!("{err}");
+                        return LinkResult::Failed("❌ Cannot process video.".to_string());
+                    }
                 }
-
-                return Response::ok("");
             }
-            Err(err) => console_error!("`db.get_video_file_id` error: {err}"),
+            Err(err) => console_error!("`db.get_media` error: {err}"),
             _ => (),
         }
     }
 
-    let platform = match Platform::new(&url) {
-        Ok(url) => url,
+    let media = match resolve_media(
+        config,
+        *http_client,
+        &platform,
+        url.as_str(),
+        &config.resolver_options(settings.quality.format_policy()),
+    )
+    .await
+    {
+        Ok(media) => media,
         Err(err) => {
-            console_error!("{err}: {url}");
-            send_bad_url_message().await;
-            return Response::ok("");
+            console_error!("{err}");
+            return LinkResult::Failed(get_resolve_error_message(&err));
         }
     };
 
-    let url = url.as_str();
-    let url = match platform {
-        Platform::TikTok => {
-            SnapUrlResolver::new(&http_client, &platform)
-                .resolve_url(url)
-                .await
+    // Lower quality variants are tried for a single video exceeding limits.
+    let mut variants = match media.as_slice() {
+        [it] if it.kind == MediaKind::Video => it.variants.clone().into_iter(),
+        _ => vec![].into_iter(),
+    };
+    let mut to_send = media.clone();
+    let mut quality = settings.quality;
+    let (messages, input_media_len) = loop {
+        let input_media = get_input_media(&to_send);
+        let input_media_len = input_media.len();
+        match tg_client
+            .send_album(
+                chat_id,
+                reply_to_message_id,
+                get_caption(link, &to_send, settings.caption_style),
+                input_media,
+            )
+            .await
+        {
+            Ok(messages) => break (messages, input_media_len),
+            Err(err) if err.is_file_too_large() => {
+                console_error!("{err}");
+                match variants.next() {
+                    Some(variant) => {
+                        console_log!("Retrying {link} with a lower quality: {}", variant.url);
+                        to_send = vec![media[0].with_variant(&variant)];
+                        quality = Quality::Low;
+                    }
+                    None => {
+                        let media = resolve_headerless_media(
+                            config,
+                            *http_client,
+                            settings.quality,
+                            &platform,
+                            url.as_str(),
+                            media,
+                        )
+                        .await;
+                        return LinkResult::TooLarge(
+                            get_direct_link_buttons(http_client, &media).await,
+                        );
+                    }
+                }
+            }
+            Err(err) => {
+                console_error!("{err}");
+                return LinkResult::Failed("❌ Cannot process video.".to_string());
+            }
         }
-        Platform::Instagram => {
-            SnapUrlResolver::new(&http_client, &platform)
-                .resolve_url(url)
-                .await
+    };
+
+    // A lower quality variant is cached for chats wanting low quality. The
+    // keys are the same for platforms without format policies.
+    let cache_key = match quality {
+        quality if quality == settings.quality => cache_key,
+        quality => get_cache_key(config, &platform, key, quality),
+    };
+    let sent_media: Vec<_> = messages.iter().filter_map(|it| it.media()).collect();
+    if let (Some(db), true) = (db, sent_media.len() == input_media_len) {
+        if let Err(err) = db.insert_media(&cache_key, &sent_media).await {
+            console_error!("`db.insert_media` error: {err}")
         }
-        Platform::Shorts => ShortsUrlResolver::new(&http_client).resolve_url(url).await,
-        Platform::Twitter => TwitterUrlResolver::new(&http_client).resolve_url(url).await,
     };
 
-    let url = match url {
-        Ok(mut url) => {
-            let query_pairs: Vec<_> = url
-                .query_pairs()
-                .filter(|(key, _)| key != "dl")
-                .map(|(key, value)| (key.into_owned(), value.into_owned()))
-                .collect();
-            url.query_pairs_mut().clear().extend_pairs(query_pairs);
-            url
+    LinkResult::Sent
+}
+
+/// Handles `/autodownload [on|off]` command in groups.
+async fn process_auto_download_command(
+    bot: &Bot<'_>,
+    chat_id: i64,
+    message: &telegram::Message,
+    args: &str,
+) {
+    let reply = |text: String| async move {
+        if let Err(err) = bot
+            .tg_client
+            .send_message(&telegram::SendMessage {
+                chat_id,
+                text,
+                reply_to_message_id: message.message_id,
+                link_preview_options: None,
+                reply_markup: None,
+            })
+            .await
+        {
+            console_error!("{err}");
+        }
+    };
+
+    let db = match bot.db {
+        Some(db) => db,
+        None => return reply("❌ Settings are not available.".to_string()).await,
+    };
+
+    let mut settings = match db.get_chat_settings(chat_id).await {
+        Ok(settings) => settings,
+        Err(err) => {
+            console_error!("`db.get_chat_settings` error: {err}");
+            return reply("❌ Cannot get settings.".to_string()).await;
+        }
+    };
+
+    let enabled = match args {
+        "" if settings.auto_download => {
+            return reply("✅ Automatic download is on.".to_string()).await
+        }
+        "" => return reply("Automatic download is off.".to_string()).await,
+        "on" => true,
+        "off" => false,
+        _ => return reply("Usage: /autodownload on|off".to_string()).await,
+    };
+
+    match is_admin(bot, chat_id, message).await {
+        Ok(true) => (),
+        Ok(false) => {
+            return reply("❌ Only administrators can change this setting.".to_string()).await
         }
         Err(err) => {
             console_error!("{err}");
+            return reply("❌ Cannot check administrator rights.".to_string()).await;
+        }
+    }
 
-            if let Err(err) = tg_client
-                .edit_message_text(&EditMessageText {
-                    chat_id: chat.id,
-                    message_id: message_to_edit.message_id,
-                    text: "❌ Cannot process video.".to_string(),
-                })
-                .await
-            {
-                console_error!("{err}");
-            }
+    settings.auto_download = enabled;
+    let text = match db.upsert_chat_settings(chat_id, &settings).await {
+        Ok(_) if enabled => "✅ Automatic download is on.".to_string(),
+        Ok(_) => "Automatic download is off.".to_string(),
+        Err(err) => {
+            console_error!("`db.upsert_chat_settings` error: {err}");
+            "❌ Cannot save settings.".to_string()
+        }
+    };
+    reply(text).await
+}
 
-            return Response::ok("");
+/// Anonymous administrators send messages on behalf of the chat itself.
+async fn is_admin(
+    bot: &Bot<'_>,
+    chat_id: i64,
+    message: &telegram::Message,
+) -> anyhow::Result<bool> {
+    if let Some(sender_chat) = &message.sender_chat {
+        return Ok(sender_chat.id == chat_id);
+    }
+
+    let user = message
+        .from
+        .as_ref()
+        .ok_or(anyhow::anyhow!("Message has no sender"))?;
+    is_user_admin(bot, chat_id, user.id).await
+}
+
+async fn is_user_admin(bot: &Bot<'_>, chat_id: i64, user_id: i64) -> anyhow::Result<bool> {
+    let member = bot
+        .tg_client
+        .get_chat_member(&GetChatMember { chat_id, user_id })
+        .await?;
+    Ok(member.is_admin())
+}
+
+/// Handles `/settings` command by sending the settings keyboard.
+async fn process_settings_command(
+    bot: &Bot<'_>,
+    chat: &telegram::Chat,
+    message: &telegram::Message,
+) {
+    let settings = match bot.db {
+        Some(db) => db.get_chat_settings(chat.id).await,
+        None => Err(anyhow::anyhow!("Database is not available")),
+    };
+    let (text, reply_markup) = match settings {
+        Ok(settings) => (
+            "⚙️ Settings",
+            Some(get_settings_keyboard(bot.config, chat, &settings)),
+        ),
+        Err(err) => {
+            console_error!("`db.get_chat_settings` error: {err}");
+            ("❌ Settings are not available.", None)
         }
     };
 
-    let video = tg_client
-        .send_video(&telegram::SendVideo {
+    if let Err(err) = bot
+        .tg_client
+        .send_message(&telegram::SendMessage {
             chat_id: chat.id,
-            video: url.to_string(),
-            reply_to_message_id: update.message_id,
-            caption: Some(message_text.clone()),
+            text: text.to_string(),
+            reply_to_message_id: message.message_id,
+            link_preview_options: None,
+            reply_markup,
         })
-        .await;
-
-    if let Err(err) = video {
+        .await
+    {
         console_error!("{err}");
+    }
+}
 
-        let message = if err.to_string() == "Bad Request: wrong file identifier/HTTP URL specified"
-        {
-            "❌ Video is too large to send it.".to_string()
-        } else {
-            "❌ Cannot process video.".to_string()
-        };
-        if let Err(err) = tg_client
-            .edit_message_text(&EditMessageText {
-                chat_id: chat.id,
-                message_id: message_to_edit.message_id,
-                text: message,
+/// Handles presses of the settings keyboard buttons. Settings of groups can
+/// be changed by administrators only.
+async fn process_callback_query(bot: &Bot<'_>, callback_query: telegram::CallbackQuery) {
+    let answer = |text: Option<&'static str>| async move {
+        if let Err(err) = bot
+            .tg_client
+            .answer_callback_query(&AnswerCallbackQuery {
+                callback_query_id: callback_query.id.clone(),
+                text: text.map(|it| it.to_string()),
+                show_alert: None,
             })
             .await
         {
             console_error!("{err}");
         }
+    };
 
-        return Response::ok("");
+    let option = callback_query
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("settings:"));
+    let (option, chat, message_id) = match (option, &callback_query.message) {
+        (
+            Some(option),
+            Some(telegram::Message {
+                chat: Some(chat),
+                message_id,
+                ..
+            }),
+        ) => (option, chat, *message_id),
+        _ => return answer(None).await,
+    };
+
+    let db = match bot.db {
+        Some(db) => db,
+        None => return answer(Some("❌ Settings are not available.")).await,
+    };
+
+    if chat.chat_type != telegram::ChatType::Private {
+        match is_user_admin(bot, chat.id, callback_query.from.id).await {
+            Ok(true) => (),
+            Ok(false) => return answer(Some("❌ Only administrators can change settings.")).await,
+            Err(err) => {
+                console_error!("{err}");
+                return answer(Some("❌ Cannot check administrator rights.")).await;
+            }
+        }
     }
 
-    let video = video.map(|it| it.video).unwrap();
+    let mut settings = match db.get_chat_settings(chat.id).await {
+        Ok(settings) => settings,
+        Err(err) => {
+            console_error!("`db.get_chat_settings` error: {err}");
+            return answer(Some("❌ Cannot get settings.")).await;
+        }
+    };
 
-    if let Err(err) = tg_client
-        .delete_message(&DeleteMessage {
+    if !toggle_setting(bot.config, &mut settings, option) {
+        return answer(None).await;
+    }
+
+    if let Err(err) = db.upsert_chat_settings(chat.id, &settings).await {
+        console_error!("`db.upsert_chat_settings` error: {err}");
+        return answer(Some("❌ Cannot save settings.")).await;
+    }
+
+    if let Err(err) = bot
+        .tg_client
+        .edit_message_reply_markup(&EditMessageReplyMarkup {
             chat_id: chat.id,
-            message_id: message_to_edit.message_id.unwrap(),
+            message_id,
+            reply_markup: get_settings_keyboard(bot.config, chat, &settings),
         })
         .await
     {
-        console_error!("{err}");
+        // Concurrent presses may leave nothing to change.
+        if !err.is_message_not_modified() {
+            console_error!("{err}");
+        }
+    }
+
+    answer(None).await
+}
+
+/// Applies a press of `settings:<option>` button. Returns `false` for unknown options.
+fn toggle_setting(config: &Config, settings: &mut ChatSettings, option: &str) -> bool {
+    match option {
+        "auto_download" => settings.auto_download = !settings.auto_download,
+        "caption_style" => {
+            settings.caption_style = match settings.caption_style {
+                CaptionStyle::Full => CaptionStyle::Link,
+                CaptionStyle::Link => CaptionStyle::None,
+                CaptionStyle::None => CaptionStyle::Full,
+            }
+        }
+        "quality" => {
+            settings.quality = match settings.quality {
+                Quality::High => Quality::Low,
+                Quality::Low => Quality::High,
+            }
+        }
+        "delete_original" => settings.delete_original = !settings.delete_original,
+        _ => {
+            let platform = match option.strip_prefix("platform:").map(str::parse) {
+                Some(Ok(platform)) => platform,
+                _ => return false,
+            };
+            let platforms = settings
+                .allowed_platforms
+                .get_or_insert_with(|| config.allowed_platforms.clone());
+            match platforms.iter().position(|it| *it == platform) {
+                Some(idx) => {
+                    platforms.remove(idx);
+                }
+                None => platforms.push(platform),
+            }
+        }
+    }
+
+    true
+}
+
+fn get_settings_keyboard(
+    config: &Config,
+    chat: &telegram::Chat,
+    settings: &ChatSettings,
+) -> InlineKeyboardMarkup {
+    let on_off = |value: bool| if value { "on" } else { "off" };
+    let button = |text: String, option: &str| InlineKeyboardButton {
+        text,
+        url: None,
+        callback_data: Some(format!("settings:{option}")),
+    };
+
+    let mut inline_keyboard = vec![];
+    if chat.chat_type != telegram::ChatType::Private {
+        inline_keyboard.push(vec![button(
+            format!("🤖 Automatic download: {}", on_off(settings.auto_download)),
+            "auto_download",
+        )]);
+    }
+
+    let caption_style = match settings.caption_style {
+        CaptionStyle::Full => "link and author",
+        CaptionStyle::Link => "link",
+        CaptionStyle::None => "none",
+    };
+    let quality = match settings.quality {
+        Quality::High => "high",
+        Quality::Low => "low",
+    };
+    inline_keyboard.push(vec![button(
+        format!("💬 Caption: {caption_style}"),
+        "caption_style",
+    )]);
+    inline_keyboard.push(vec![button(format!("🎞 Quality: {quality}"), "quality")]);
+    inline_keyboard.push(vec![button(
+        format!(
+            "🗑 Delete link message: {}",
+            on_off(settings.delete_original)
+        ),
+        "delete_original",
+    )]);
+
+    for platforms in config.allowed_platforms.chunks(3) {
+        inline_keyboard.push(
+            platforms
+                .iter()
+                .map(|platform| {
+                    let mark = if settings.is_platform_allowed(platform) {
+                        "✅"
+                    } else {
+                        "❌"
+                    };
+                    button(
+                        format!("{mark} {}", platform.name()),
+                        &format!("platform:{}", platform.name().to_lowercase()),
+                    )
+                })
+                .collect(),
+        );
+    }
+
+    InlineKeyboardMarkup { inline_keyboard }
+}
+
+/// Returns settings of `chat_id`, defaults when they cannot be loaded.
+async fn get_chat_settings(db: &Option<Db>, chat_id: i64) -> ChatSettings {
+    match db {
+        Some(db) => db.get_chat_settings(chat_id).await.unwrap_or_else(|err| {
+            console_error!("`db.get_chat_settings` error: {err}");
+            ChatSettings::default()
+        }),
+        None => ChatSettings::default(),
+    }
+}
+
+async fn process_inline_query(
+    inline_query: telegram::InlineQuery,
+    config: &Config,
+    db: &Option<Db>,
+    http_client: &reqwest::Client,
+    tg_client: &Telegram<'_>,
+) -> Result<Response> {
+    let query = inline_query.query.trim();
+    if query.is_empty() {
+        return Response::ok("");
+    }
+
+    let answer_inline_query = |results: Vec<InlineQueryResult>, cache_time: Option<i64>| async move {
+        if let Err(err) = tg_client
+            .answer_inline_query(&AnswerInlineQuery {
+                inline_query_id: inline_query.id.clone(),
+                results,
+                cache_time,
+            })
+            .await
+        {
+            console_error!("{err}");
+        }
+    };
+
+    let url = match parse_url(query) {
+        Ok(url) => url,
+        Err(_) => {
+            answer_inline_query(vec![], None).await;
+            return Response::ok("");
+        }
+    };
+
+    let CanonicalUrl {
+        url,
+        key: cache_key,
+    } = match canonicalize(http_client, url).await {
+        Ok(canonical_url) => canonical_url,
+        Err(err) => {
+            console_error!("{err}");
+            answer_inline_query(vec![], None).await;
+            return Response::ok("");
+        }
+    };
+
+    // Checked before the cache, so disallowed platforms are not served from it.
+    let platform = match Platform::new(&url) {
+        Ok(platform) if config.is_platform_allowed(&platform) => platform,
+        Ok(platform) => {
+            console_error!("{platform:?} is not allowed: {url}");
+            answer_inline_query(vec![], None).await;
+            return Response::ok("");
+        }
+        Err(err) => {
+            console_error!("{err}: {url}");
+            answer_inline_query(vec![], None).await;
+            return Response::ok("");
+        }
+    };
+
+    // Inline results are always sent in the default quality.
+    let cache_key = get_cache_key(config, &platform, cache_key, Quality::High);
+
+    if let Some(db) = db {
+        match db.get_media(&cache_key, config.cache_ttl()).await {
+            Ok(media) if !media.is_empty() => {
+                let results = media
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(idx, it)| match it.kind {
+                        MediaKind::Video => Some(InlineQueryResult::CachedVideo(
+                            InlineQueryResultCachedVideo {
+                                result_type: InlineQueryResultType::Video,
+                                id: idx.to_string(),
+                                video_file_id: it.file_id,
+                                title: query.to_string(),
+                                caption: Some(query.to_string()),
+                            },
+                        )),
+                        MediaKind::Photo => Some(InlineQueryResult::CachedPhoto(
+                            InlineQueryResultCachedPhoto {
+                                result_type: InlineQueryResultType::Photo,
+                                id: idx.to_string(),
+                                photo_file_id: it.file_id,
+                                caption: Some(query.to_string()),
+                            },
+                        )),
+                        MediaKind::Audio => None,
+                    })
+                    .collect();
+                answer_inline_query(results, None).await;
+                if let Err(err) = db.touch_media(&cache_key).await {
+                    console_error!("`db.touch_media` error: {err}");
+                }
+
+                return Response::ok("");
+            }
+            Err(err) => console_error!("`db.get_media` error: {err}"),
+            _ => (),
+        }
     }
 
-    if let (Some(db), Some(video)) = (&db, &video) {
-        if let Err(err) = db.insert_video(&message_text, &video.file_id).await {
-            console_error!("`db.insert_video_file_id` error: {err}")
+    let media = match resolve_inline_media(config, http_client, &platform, url.as_str()).await {
+        Ok(media) => media,
+        Err(err) => {
+            console_error!("{err}");
+            answer_inline_query(vec![], None).await;
+            return Response::ok("");
         }
     };
 
+    // Telegram must not serve results with expired URLs from its cache.
+    let cache_time = media
+        .iter()
+        .filter_map(|it| it.expires_at)
+        .min()
+        .map(|expires_at| {
+            (expires_at as i64 - (Date::now().as_millis() / 1000) as i64)
+                .clamp(0, INLINE_CACHE_TIME)
+        });
+    let results = get_inline_results(media, query);
+    answer_inline_query(results, cache_time).await;
+
     Response::ok("")
 }
+
+/// Resolves media Telegram can download by URL itself for inline results.
+async fn resolve_inline_media<C: HttpClient>(
+    config: &Config,
+    http_client: &C,
+    platform: &Platform,
+    url: &str,
+) -> anyhow::Result<Vec<ResolvedMedia>> {
+    // Matches the quality of the inline cache key.
+    let options = ResolverOptions {
+        headerless: true,
+        ..config.resolver_options(Quality::High.format_policy())
+    };
+    resolve_media(config, http_client, platform, url, &options).await
+}
+
+/// Converts media resolved for `query` into inline results.
+fn get_inline_results(media: Vec<ResolvedMedia>, query: &str) -> Vec<InlineQueryResult> {
+    media
+        .into_iter()
+        .enumerate()
+        // Results need a JPEG thumbnail, media without one is not offered.
+        .filter_map(|(idx, it)| match it.kind {
+            MediaKind::Video => Some(InlineQueryResult::Video(InlineQueryResultVideo {
+                result_type: InlineQueryResultType::Video,
+                id: idx.to_string(),
+                video_url: it.url.to_string(),
+                mime_type: "video/mp4".to_string(),
+                thumbnail_url: it.thumbnail.filter(is_jpeg)?.to_string(),
+                title: it.title.unwrap_or(query.to_string()),
+                caption: Some(query.to_string()),
+            })),
+            MediaKind::Photo if is_jpeg(&it.url) => {
+                Some(InlineQueryResult::Photo(InlineQueryResultPhoto {
+                    result_type: InlineQueryResultType::Photo,
+                    id: idx.to_string(),
+                    photo_url: it.url.to_string(),
+                    thumbnail_url: it.url.to_string(),
+                    caption: Some(query.to_string()),
+                }))
+            }
+            MediaKind::Photo | MediaKind::Audio => None,
+        })
+        .collect()
+}
+
+/// Username fetched via `getMe`, kept for the lifetime of the isolate.
+static BOT_USERNAME: OnceLock<String> = OnceLock::new();
+
+/// Returns `BOT_USERNAME` or asks Telegram once per isolate.
+async fn get_bot_username(config: &Config, tg_client: &Telegram<'_>) -> anyhow::Result<String> {
+    if let Some(username) = config.bot_username.as_ref().or(BOT_USERNAME.get()) {
+        return Ok(username.clone());
+    }
+
+    let username = tg_client
+        .get_me()
+        .await?
+        .username
+        .ok_or(anyhow::anyhow!("Bot has no username"))?;
+    Ok(BOT_USERNAME.get_or_init(|| username).clone())
+}
+
+/// Splits `/command@username args` into command and args. Returns `None`
+/// for non-commands and commands addressed to other bots.
+fn parse_command<'a>(text: &'a str, bot_username: &str) -> Option<(&'a str, &'a str)> {
+    let text = text.strip_prefix('/')?;
+    let (command, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let command = match command.split_once('@') {
+        Some((command, username)) if username.eq_ignore_ascii_case(bot_username) => command,
+        Some(_) => return None,
+        None => command,
+    };
+    Some((command, args.trim()))
+}
+
+/// Checks that `link` belongs to an allowed platform without any network
+/// requests. Short links are accepted by the platform of their host and
+/// checked again after expansion.
+fn is_supported_link(config: &Config, settings: &ChatSettings, link: &str) -> bool {
+    match parse_url(link).map(|url| Platform::new(&url)) {
+        Ok(Ok(platform)) => {
+            config.is_platform_allowed(&platform) && settings.is_platform_allowed(&platform)
+        }
+        _ => false,
+    }
+}
+
+fn get_skipped_links_message(max_links: usize, skipped_links: usize) -> String {
+    format!("⚠️ Only the first {max_links} links of a message are processed, {skipped_links} skipped. Send them in another message.")
+}
+
+fn get_bad_url_message(config: &Config, settings: &ChatSettings) -> String {
+    let names: Vec<_> = config
+        .allowed_platforms
+        .iter()
+        .filter(|platform| settings.is_platform_allowed(platform))
+        .map(|platform| platform.name())
+        .collect();
+    let names = match names.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+        None => return "❌ No platforms are allowed in this chat.".to_string(),
+    };
+    format!("❌ Only {names} links are accepted.")
+}
+
+fn get_resolve_error_message(err: &anyhow::Error) -> String {
+    let is_decode_error = |err: &anyhow::Error| err.downcast_ref::<DecodeError>().is_some();
+    let is_malformed_response = match err.downcast_ref::<ChainError>() {
+        Some(chain_error) => chain_error
+            .errors
+            .iter()
+            .any(|(_, err)| is_decode_error(err)),
+        None => is_decode_error(err),
+    };
+
+    if is_malformed_response {
+        "❌ Cannot process video: download service returned an unexpected response.".to_string()
+    } else {
+        "❌ Cannot process video.".to_string()
+    }
+}
+
+/// Returns buttons with direct links to videos, labeled with quality and
+/// size when they are known. Videos needing headers, e.g. YouTube URLs
+/// bound to the IP address of the worker, cannot be opened by users and get
+/// no button.
+/// Direct links are opened without headers, so `media` needing them is
+/// resolved again by the next resolver of the chain returning public URLs,
+/// e.g. snaptik for TikTok. Falls back to `media` when there is none.
+async fn resolve_headerless_media<C: HttpClient>(
+    config: &Config,
+    http_client: &C,
+    quality: Quality,
+    platform: &Platform,
+    url: &str,
+    media: Vec<ResolvedMedia>,
+) -> Vec<ResolvedMedia> {
+    if media.iter().all(|it| it.headers.is_empty()) {
+        return media;
+    }
+
+    let options = ResolverOptions {
+        headerless: true,
+        ..config.resolver_options(quality.format_policy())
+    };
+    match resolve_media(config, http_client, platform, url, &options).await {
+        Ok(headerless_media) => headerless_media,
+        Err(err) => {
+            console_error!("Cannot resolve direct links: {err}");
+            media
+        }
+    }
+}
+
+async fn get_direct_link_buttons(
+    http_client: &reqwest::Client,
+    media: &[ResolvedMedia],
+) -> Vec<InlineKeyboardButton> {
+    let mut buttons = vec![];
+    for it in media
+        .iter()
+        .filter(|it| it.kind == MediaKind::Video && it.headers.is_empty())
+    {
+        let size = match it.size {
+            Some(size) => Some(size),
+            None => match http_client.head(it.url.as_str()).send().await {
+                Ok(response) => response.content_length(),
+                Err(err) => {
+                    console_error!("{err}");
+                    None
+                }
+            },
+        };
+
+        let mut details = vec![];
+        if let Some(height) = it.height {
+            details.push(format!("{height}p"));
+        }
+        if let Some(size) = size.filter(|it| *it > 0) {
+            details.push(format!("{:.1} MB", size as f64 / 1024.0 / 1024.0));
+        }
+        let text = if details.is_empty() {
+            "⬇️ Download".to_string()
+        } else {
+            format!("⬇️ Download ({})", details.join(", "))
+        };
+
+        buttons.push(InlineKeyboardButton {
+            text,
+            url: Some(it.url.to_string()),
+            callback_data: None,
+        });
+    }
+    buttons
+}
+
+/// Converts resolved media into items to send, adding separate audio tracks
+/// of video-only streams as audio items.
+fn get_input_media(media: &[ResolvedMedia]) -> Vec<InputMedia> {
+    let mut input_media: Vec<_> = media.iter().map(InputMedia::from).collect();
+    input_media.extend(
+        media
+            .iter()
+            .filter_map(|it| Some((it.audio_url.as_ref()?, &it.headers)))
+            .map(|(url, headers)| {
+                InputMedia::new(MediaKind::Audio, url.to_string()).with_headers(headers.clone())
+            }),
+    );
+    input_media
+}
+
+fn get_caption(text: &str, media: &[ResolvedMedia], style: CaptionStyle) -> Option<String> {
+    match style {
+        CaptionStyle::Full => {
+            let mut caption = text.to_string();
+            if let Some(title) = media.iter().find_map(|it| it.title.as_ref()) {
+                let title = match title.char_indices().nth(MAX_TITLE_LENGTH) {
+                    Some((idx, _)) => format!("{}…", &title[..idx]),
+                    None => title.clone(),
+                };
+                caption.push_str(&format!("\n\n{title}"));
+            }
+            if let Some(author) = media.iter().find_map(|it| it.author.as_ref()) {
+                caption.push_str(&format!("\n\n👤 @{author}"));
+            }
+            Some(caption)
+        }
+        CaptionStyle::Link => Some(text.to_string()),
+        CaptionStyle::None => None,
+    }
+}
+
+/// Whether `url` points to a JPEG image, judging by its extension or the
+/// `format` query parameter of image CDNs.
+fn is_jpeg(url: &Url) -> bool {
+    let path = url.path().to_ascii_lowercase();
+    path.ends_with(".jpg")
+        || path.ends_with(".jpeg")
+        || url
+            .query_pairs()
+            .any(|(name, value)| name == "format" && matches!(&*value, "jpg" | "jpeg" | "pjpg"))
+}
+
+fn parse_url(text: &str) -> std::result::Result<Url, url::ParseError> {
+    let mut url = Url::parse(text)?;
+
+    let mut url_path = url.path().to_owned();
+    if !url_path.ends_with('/') {
+        url_path.push('/');
+        url.set_path(&url_path);
+    }
+
+    Ok(url)
+}
+
+/// Appends `quality` to `key` when the resolvers of `platform` pick formats
+/// by it, so media of a lower quality is not served to chats wanting a higher
+/// one and vice versa.
+fn get_cache_key(config: &Config, platform: &Platform, key: String, quality: Quality) -> String {
+    let uses_format_policy = config
+        .resolver_chain(platform)
+        .iter()
+        .any(Resolver::uses_format_policy);
+    match quality {
+        Quality::Low if uses_format_policy => format!("{key}:{}", quality.as_str()),
+        _ => key,
+    }
+}
+
+async fn resolve_media<C: HttpClient>(
+    config: &Config,
+    http_client: &C,
+    platform: &Platform,
+    url: &str,
+    options: &ResolverOptions,
+) -> anyhow::Result<Vec<ResolvedMedia>> {
+    let mut media = resolve_with_fallback(
+        http_client,
+        config.resolver_chain(platform),
+        url,
+        config.resolver_timeout(),
+        options,
+    )
+    .await?;
+
+    for it in media.iter_mut() {
+        let query_pairs: Vec<_> = it
+            .url
+            .query_pairs()
+            .filter(|(key, _)| key != "dl")
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        it.url.query_pairs_mut().clear().extend_pairs(query_pairs);
+    }
+
+    Ok(media)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    const TIKTOK_URL: &str = "https://www.tiktok.com/@catlover/video/7300000000000000001";
+
+    fn config() -> Config {
+        Config {
+            api_path: "https://api.telegram.org/bot123:abc".to_string(),
+            webhook_secret: None,
+            admin_token: None,
+            webhook_base_url: None,
+            bot_username: None,
+            db_binding: "DB".to_string(),
+            allowed_platforms: Platform::ALL.to_vec(),
+            resolvers: HashMap::from(Platform::ALL.map(|it| (it, Resolver::default_chain(&it)))),
+            resolver_timeout_ms: 15_000,
+            youtube_max_duration: 600,
+            max_concurrent_links: 3,
+            max_links_per_message: 10,
+            cache_ttl_days: 30,
+            health_check_urls: vec![],
+            admin_chat_id: None,
+        }
+    }
+
+    #[test]
+    fn resolves_inline_tiktok_results_without_headers() {
+        let client = FakeHttpClient::new()
+            .fixture(TIKTOK_URL, "tiktok/video.html")
+            .fixture("https://snaptik.app/en", "snap/token.html")
+            .fixture("https://snaptik.app/abc2.php", "snap/snaptik_result.js");
+        let media = block_on(resolve_inline_media(
+            &config(),
+            &client,
+            &Platform::TikTok,
+            TIKTOK_URL,
+        ))
+        .unwrap();
+        let results = get_inline_results(media, TIKTOK_URL);
+
+        assert_eq!(results.len(), 1);
+        let InlineQueryResult::Video(video) = &results[0] else {
+            panic!("Expected a video result: {results:?}");
+        };
+        assert_eq!(video.video_url, "https://d.rapidcdn.app/v2?token=video-hd");
+        assert_eq!(
+            video.thumbnail_url,
+            "https://p16-sign.tiktokcdn.com/obj/cover.jpeg?x-expires=1700000000"
+        );
+        assert_eq!(video.title, "Cats #fyp");
+    }
+
+    #[test]
+    fn resolves_direct_links_of_media_needing_headers() {
+        let client = FakeHttpClient::new()
+            .fixture(TIKTOK_URL, "tiktok/video.html")
+            .fixture("https://snaptik.app/en", "snap/token.html")
+            .fixture("https://snaptik.app/abc2.php", "snap/snaptik_result.js");
+        let mut video = ResolvedMedia::new(
+            MediaKind::Video,
+            Url::parse("https://v16-webapp.tiktok.com/video.mp4").unwrap(),
+        );
+        video.headers = vec![("Cookie".to_string(), "tt_chain_token=abc".to_string())];
+        let media = block_on(resolve_headerless_media(
+            &config(),
+            &client,
+            Quality::High,
+            &Platform::TikTok,
+            TIKTOK_URL,
+            vec![video],
+        ));
+
+        assert_eq!(media.len(), 1);
+        assert_eq!(
+            media[0].url.as_str(),
+            "https://d.rapidcdn.app/v2?token=video-hd"
+        );
+        assert!(media[0].headers.is_empty());
+    }
+
+    #[test]
+    fn offers_inline_results_with_jpeg_thumbnails_only() {
+        let video = |thumbnail: &str| {
+            let mut video = ResolvedMedia::new(
+                MediaKind::Video,
+                Url::parse("https://v.redd.it/abc/DASH_720.mp4").unwrap(),
+            );
+            video.thumbnail = Some(Url::parse(thumbnail).unwrap());
+            video
+        };
+        let media = vec![
+            video("https://external-preview.redd.it/abc.jpg?width=640"),
+            video("https://p16-sign.tiktokcdn.com/obj/cover.webp"),
+            ResolvedMedia::new(
+                MediaKind::Video,
+                Url::parse("https://v.redd.it/def/DASH_720.mp4").unwrap(),
+            ),
+            ResolvedMedia::new(
+                MediaKind::Photo,
+                Url::parse("https://i.redd.it/ghi.png").unwrap(),
+            ),
+        ];
+        let results = get_inline_results(media, "https://redd.it/abc");
+
+        let ids: Vec<_> = results
+            .iter()
+            .map(|it| match it {
+                InlineQueryResult::Video(it) => it.id.as_str(),
+                _ => panic!("Expected a video result: {it:?}"),
+            })
+            .collect();
+        assert_eq!(ids, ["0"]);
+    }
+
+    #[test]
+    fn detects_jpeg_urls() {
+        let cases = [
+            ("https://pbs.twimg.com/media/quoted.jpg", true),
+            ("https://i.redd.it/ghi789.JPEG", true),
+            (
+                "https://preview.redd.it/img1.jpg?width=1920&format=pjpg&s=abc",
+                true,
+            ),
+            (
+                "https://pbs.twimg.com/media/AbCdEf?format=jpg&name=large",
+                true,
+            ),
+            ("https://i.redd.it/abc.png", false),
+            (
+                "https://p16-sign.tiktokcdn.com/obj/photo.webp?x-expires=1700000000",
+                false,
+            ),
+            ("https://preview.redd.it/gif2.gif?format=mp4&s=def", false),
+            (
+                "https://v16-webapp-prime.tiktok.com/video/tos/1080.mp4",
+                false,
+            ),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(is_jpeg(&Url::parse(url).unwrap()), expected, "{url}");
+        }
+    }
+}
@@ -1,6 +1,33 @@
-use anyhow::{anyhow, Result};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::StreamExt;
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use url::Url;
+use worker::{console_error, console_log, Delay};
+
+use crate::url_resolver::{MediaKind, ResolvedMedia};
+
+/// Telegram accepts from 2 to 10 items in a single media group.
+const MEDIA_GROUP_LIMIT: usize = 10;
+
+/// Maximum number of attempts of a single request.
+const MAX_ATTEMPTS: usize = 3;
+
+/// Maximum total time spent waiting for rate limits of a single request, so
+/// the update is still answered before Telegram gives up on the webhook.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(20);
+
+/// Bytes Telegram fetches by URL itself.
+pub const URL_LIMIT: usize = 20 * 1024 * 1024;
+
+/// Bytes Telegram accepts in uploads.
+pub const UPLOAD_LIMIT: usize = 50 * 1024 * 1024;
+
+static BOUNDARY: &str = "----SnaptikBotFormBoundary7MA4YWxkTrZu0gW";
+
+static USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/119.0.0.0 Safari/537.36";
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
 pub enum ChatType {
@@ -17,6 +44,71 @@ pub enum ChatType {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct SetWebhook<'a> {
     pub url: &'a str,
+    pub secret_token: &'a str,
+}
+
+/// Header Telegram sends with every webhook request when `secret_token` is set.
+pub const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+/// Checks `X-Telegram-Bot-Api-Secret-Token` header value against the token
+/// passed to `setWebhook`. Always succeeds when no token is configured.
+pub fn verify_secret_token(expected: Option<&str>, actual: Option<&str>) -> bool {
+    let expected = match expected {
+        Some(expected) => expected.as_bytes(),
+        None => return true,
+    };
+    let actual = match actual {
+        Some(actual) => actual.as_bytes(),
+        None => return false,
+    };
+
+    if expected.len() != actual.len() {
+        return false;
+    }
+
+    expected
+        .iter()
+        .zip(actual)
+        .fold(0, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct User {
+    pub id: i64,
+    pub is_bot: bool,
+    pub username: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatMemberStatus {
+    Creator,
+    Administrator,
+    Member,
+    Restricted,
+    Left,
+    Kicked,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ChatMember {
+    pub status: ChatMemberStatus,
+}
+
+impl ChatMember {
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self.status,
+            ChatMemberStatus::Creator | ChatMemberStatus::Administrator
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GetChatMember {
+    pub chat_id: i64,
+    pub user_id: i64,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -29,15 +121,117 @@ pub struct Chat {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Message {
     pub message_id: Option<isize>,
+    pub from: Option<User>,
+    pub sender_chat: Option<Chat>,
     pub chat: Option<Chat>,
     pub text: Option<String>,
     pub reply_to_message: Option<Box<Message>>,
+    /// The group has been upgraded to a supergroup with this ID.
+    pub migrate_to_chat_id: Option<i64>,
     pub video: Option<Video>,
+    pub photo: Option<Vec<PhotoSize>>,
+    pub audio: Option<Audio>,
+    pub entities: Option<Vec<MessageEntity>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageEntityType {
+    Url,
+    TextLink,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MessageEntity {
+    #[serde(rename = "type")]
+    pub entity_type: MessageEntityType,
+    /// Offset in UTF-16 code units.
+    pub offset: usize,
+    /// Length in UTF-16 code units.
+    pub length: usize,
+    pub url: Option<String>,
+}
+
+impl Message {
+    /// Returns unique links of `url` and `text_link` entities. Falls back to
+    /// the whole text when it has no entities but is a URL itself.
+    pub fn links(&self) -> Vec<String> {
+        let text = self.text.as_deref().unwrap_or_default();
+        let text_utf16: Vec<u16> = text.encode_utf16().collect();
+
+        let mut links: Vec<String> = vec![];
+        for entity in self.entities.iter().flatten() {
+            let link = match entity.entity_type {
+                MessageEntityType::Url => text_utf16
+                    .get(entity.offset..entity.offset + entity.length)
+                    .map(String::from_utf16_lossy),
+                MessageEntityType::TextLink => entity.url.clone(),
+                MessageEntityType::Other => None,
+            };
+            let link = match link {
+                Some(link) if link.contains("://") => link,
+                Some(link) => format!("https://{link}"),
+                None => continue,
+            };
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+
+        if links.is_empty() && url::Url::parse(text.trim()).is_ok() {
+            links.push(text.trim().to_string());
+        }
+
+        links
+    }
+
+    /// Returns the attached video, audio or the largest photo.
+    pub fn media(&self) -> Option<MediaFile<'_>> {
+        let (kind, file_id, file_unique_id) = if let Some(video) = &self.video {
+            (MediaKind::Video, &video.file_id, &video.file_unique_id)
+        } else if let Some(photo) = self.photo.as_ref().and_then(|sizes| sizes.last()) {
+            (MediaKind::Photo, &photo.file_id, &photo.file_unique_id)
+        } else {
+            let audio = self.audio.as_ref()?;
+            (MediaKind::Audio, &audio.file_id, &audio.file_unique_id)
+        };
+
+        Some(MediaFile {
+            kind,
+            file_id,
+            file_unique_id,
+        })
+    }
+}
+
+/// File attached to a message.
+#[derive(Debug)]
+pub struct MediaFile<'a> {
+    pub kind: MediaKind,
+    /// Identifier to send the file again.
+    pub file_id: &'a str,
+    /// Identifier that is the same for every bot and over time.
+    pub file_unique_id: &'a str,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Video {
     pub file_id: String,
+    pub file_unique_id: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PhotoSize {
+    pub file_id: String,
+    pub file_unique_id: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Audio {
+    pub file_id: String,
+    pub file_unique_id: String,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -45,10 +239,41 @@ pub struct LinkPreviewOptions {
     pub is_disabled: Option<bool>,
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InlineQuery {
+    pub id: String,
+    pub query: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CallbackQuery {
+    pub id: String,
+    pub from: User,
+    /// Message with the pressed button.
+    pub message: Option<Message>,
+    pub data: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Update {
     pub update_id: isize,
     pub message: Option<Message>,
+    pub inline_query: Option<InlineQuery>,
+    pub callback_query: Option<CallbackQuery>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct InlineKeyboardButton {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_data: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InlineKeyboardMarkup {
+    pub inline_keyboard: Vec<Vec<InlineKeyboardButton>>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -58,6 +283,8 @@ pub struct SendMessage {
     pub reply_to_message_id: Option<isize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub link_preview_options: Option<LinkPreviewOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -66,6 +293,152 @@ pub struct SendVideo {
     pub video: String,
     pub reply_to_message_id: Option<isize>,
     pub caption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u32>,
+    /// Headers needed to download `video` by URL, not sent to Telegram.
+    #[serde(skip)]
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SendPhoto {
+    pub chat_id: i64,
+    pub photo: String,
+    pub reply_to_message_id: Option<isize>,
+    pub caption: Option<String>,
+    /// Headers needed to download `photo` by URL, not sent to Telegram.
+    #[serde(skip)]
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SendAudio {
+    pub chat_id: i64,
+    pub audio: String,
+    pub reply_to_message_id: Option<isize>,
+    pub caption: Option<String>,
+    /// Headers needed to download `audio` by URL, not sent to Telegram.
+    #[serde(skip)]
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum InputMedia {
+    Video {
+        media: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        caption: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        width: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        height: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration: Option<u32>,
+        #[serde(skip)]
+        headers: Vec<(String, String)>,
+    },
+    Photo {
+        media: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        caption: Option<String>,
+        #[serde(skip)]
+        headers: Vec<(String, String)>,
+    },
+    Audio {
+        media: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        caption: Option<String>,
+        #[serde(skip)]
+        headers: Vec<(String, String)>,
+    },
+}
+
+impl InputMedia {
+    pub fn new(kind: MediaKind, media: String) -> Self {
+        match kind {
+            MediaKind::Video => Self::Video {
+                media,
+                caption: None,
+                width: None,
+                height: None,
+                duration: None,
+                headers: vec![],
+            },
+            MediaKind::Photo => Self::Photo {
+                media,
+                caption: None,
+                headers: vec![],
+            },
+            MediaKind::Audio => Self::Audio {
+                media,
+                caption: None,
+                headers: vec![],
+            },
+        }
+    }
+
+    /// Sets headers needed to download the media by URL.
+    pub fn with_headers(mut self, value: Vec<(String, String)>) -> Self {
+        match &mut self {
+            Self::Video { headers, .. }
+            | Self::Photo { headers, .. }
+            | Self::Audio { headers, .. } => *headers = value,
+        }
+        self
+    }
+
+    fn media(&self) -> &str {
+        match self {
+            Self::Video { media, .. } | Self::Photo { media, .. } | Self::Audio { media, .. } => {
+                media
+            }
+        }
+    }
+
+    fn headers(&self) -> &[(String, String)] {
+        match self {
+            Self::Video { headers, .. }
+            | Self::Photo { headers, .. }
+            | Self::Audio { headers, .. } => headers,
+        }
+    }
+
+    fn set_caption(&mut self, value: Option<String>) {
+        match self {
+            Self::Video { caption, .. }
+            | Self::Photo { caption, .. }
+            | Self::Audio { caption, .. } => *caption = value,
+        }
+    }
+}
+
+impl From<&ResolvedMedia> for InputMedia {
+    fn from(value: &ResolvedMedia) -> Self {
+        match Self::new(value.kind, value.url.to_string()) {
+            Self::Video { media, caption, .. } => Self::Video {
+                media,
+                caption,
+                width: value.width,
+                height: value.height,
+                duration: value.duration,
+                headers: vec![],
+            },
+            input_media => input_media,
+        }
+        .with_headers(value.headers.clone())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SendMediaGroup {
+    pub chat_id: i64,
+    pub media: Vec<InputMedia>,
+    pub reply_to_message_id: Option<isize>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -73,6 +446,24 @@ pub struct EditMessageText {
     pub chat_id: i64,
     pub message_id: Option<isize>,
     pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EditMessageReplyMarkup {
+    pub chat_id: i64,
+    pub message_id: Option<isize>,
+    pub reply_markup: InlineKeyboardMarkup,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AnswerCallbackQuery {
+    pub callback_query_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_alert: Option<bool>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -81,6 +472,81 @@ pub struct DeleteMessage {
     pub message_id: isize,
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+pub enum InlineQueryResultType {
+    #[serde(rename = "video")]
+    Video,
+    #[serde(rename = "photo")]
+    Photo,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InlineQueryResultVideo {
+    #[serde(rename = "type")]
+    pub result_type: InlineQueryResultType,
+    pub id: String,
+    pub video_url: String,
+    pub mime_type: String,
+    pub thumbnail_url: String,
+    pub title: String,
+    pub caption: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InlineQueryResultCachedVideo {
+    #[serde(rename = "type")]
+    pub result_type: InlineQueryResultType,
+    pub id: String,
+    pub video_file_id: String,
+    pub title: String,
+    pub caption: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InlineQueryResultPhoto {
+    #[serde(rename = "type")]
+    pub result_type: InlineQueryResultType,
+    pub id: String,
+    pub photo_url: String,
+    pub thumbnail_url: String,
+    pub caption: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InlineQueryResultCachedPhoto {
+    #[serde(rename = "type")]
+    pub result_type: InlineQueryResultType,
+    pub id: String,
+    pub photo_file_id: String,
+    pub caption: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+pub enum InlineQueryResult {
+    Video(InlineQueryResultVideo),
+    CachedVideo(InlineQueryResultCachedVideo),
+    Photo(InlineQueryResultPhoto),
+    CachedPhoto(InlineQueryResultCachedPhoto),
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AnswerInlineQuery {
+    pub inline_query_id: String,
+    pub results: Vec<InlineQueryResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_time: Option<i64>,
+}
+
+/// Additional info about a failed request.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct ResponseParameters {
+    /// Seconds to wait before repeating a rate limited request.
+    pub retry_after: Option<u64>,
+    /// The group has been migrated to a supergroup with this ID.
+    pub migrate_to_chat_id: Option<i64>,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(untagged)]
 pub enum Response<T> {
@@ -90,9 +556,119 @@ pub enum Response<T> {
     Err {
         error_code: i64,
         description: String,
+        parameters: Option<ResponseParameters>,
     },
 }
 
+/// Error of a Bot API request.
+#[derive(Debug)]
+pub enum TelegramError {
+    /// Request cannot be sent or its response cannot be read.
+    Http(reqwest::Error),
+    /// Bot API responded with an error.
+    Api {
+        error_code: i64,
+        description: String,
+        parameters: ResponseParameters,
+    },
+    /// Request cannot be serialized or response has unexpected format.
+    Decode(serde_json::Error),
+    /// Files downloaded for an upload take this many bytes, more than
+    /// [`UPLOAD_LIMIT`].
+    TooLarge(usize),
+}
+
+impl TelegramError {
+    /// Time to wait before repeating a `429 Too Many Requests` request.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Api {
+                error_code: 429,
+                parameters,
+                ..
+            } => parameters.retry_after.map(Duration::from_secs),
+            _ => None,
+        }
+    }
+
+    /// ID of the supergroup the group has been upgraded to.
+    pub fn migrate_to_chat_id(&self) -> Option<i64> {
+        match self {
+            Self::Api { parameters, .. } => parameters.migrate_to_chat_id,
+            _ => None,
+        }
+    }
+
+    /// Files are too large to be downloaded for an upload or the upload
+    /// itself is rejected by Telegram.
+    pub fn is_file_too_large(&self) -> bool {
+        matches!(self, Self::TooLarge(_))
+            || self.is_api_error(400, "file is too big")
+            || self.is_api_error(413, "")
+    }
+
+    /// Telegram cannot fetch the file by URL, e.g. it exceeds 20MB or the
+    /// server rejects Telegram. Telegram also answers unknown `file_id`s with
+    /// `wrong file identifier/HTTP URL specified`, requests sending only
+    /// `file_id`s treat this error as [`Self::is_invalid_file_id`].
+    pub fn is_url_rejected(&self) -> bool {
+        self.is_api_error(400, "wrong file identifier/HTTP URL specified")
+            || self.is_api_error(400, "failed to get HTTP URL content")
+            || self.is_api_error(400, "wrong type of the web page content")
+    }
+
+    /// A previously received `file_id` is no longer valid. Never matches
+    /// errors of [`Self::is_url_rejected`].
+    pub fn is_invalid_file_id(&self) -> bool {
+        (self.is_api_error(400, "wrong file identifier")
+            && !self.is_api_error(400, "wrong file identifier/HTTP URL specified"))
+            || self.is_api_error(400, "wrong remote file identifier")
+            || self.is_api_error(400, "file reference expired")
+            || self.is_api_error(400, "FILE_REFERENCE_EXPIRED")
+    }
+
+    pub fn is_message_not_modified(&self) -> bool {
+        self.is_api_error(400, "message is not modified")
+    }
+
+    pub fn is_bot_blocked(&self) -> bool {
+        self.is_api_error(403, "bot was blocked by the user")
+            || self.is_api_error(403, "bot was kicked")
+    }
+
+    pub fn is_chat_not_found(&self) -> bool {
+        self.is_api_error(400, "chat not found")
+    }
+
+    fn is_api_error(&self, code: i64, pattern: &str) -> bool {
+        matches!(
+            self,
+            Self::Api { error_code, description, .. }
+                if *error_code == code && description.contains(pattern)
+        )
+    }
+}
+
+impl std::fmt::Display for TelegramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Http(err) => write!(f, "Telegram request failed: {err}"),
+            Self::Api {
+                error_code,
+                description,
+                ..
+            } => write!(f, "Telegram error {error_code}: {description}"),
+            Self::Decode(err) => write!(f, "Unexpected Telegram response: {err}"),
+            Self::TooLarge(size) => write!(
+                f,
+                "Files take {size} bytes, only {UPLOAD_LIMIT} can be uploaded"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TelegramError {}
+
 pub struct Telegram<'a> {
     client: &'a Client,
     api_path: &'a str,
@@ -103,66 +679,644 @@ impl<'a> Telegram<'a> {
         Self { client, api_path }
     }
 
-    pub async fn set_webhook(&self, webhook: &SetWebhook<'_>) -> Result<String> {
-        self.client
-            .post(self.api_path.to_owned() + "/setWebhook")
-            .json(&webhook)
-            .send()
-            .await?
-            .text()
+    pub async fn set_webhook(&self, webhook: &SetWebhook<'_>) -> Result<bool, TelegramError> {
+        self.request("setWebhook", webhook).await
+    }
+
+    pub async fn get_me(&self) -> Result<User, TelegramError> {
+        self.request("getMe", &serde_json::json!({})).await
+    }
+
+    pub async fn get_chat_member(
+        &self,
+        get_chat_member: &GetChatMember,
+    ) -> Result<ChatMember, TelegramError> {
+        self.request("getChatMember", get_chat_member).await
+    }
+
+    pub async fn send_message(&self, message: &SendMessage) -> Result<Message, TelegramError> {
+        self.request("sendMessage", message).await
+    }
+
+    pub async fn send_video(&self, video: &SendVideo) -> Result<Message, TelegramError> {
+        self.request_with_upload("sendVideo", video, &[(&video.video, &video.headers)])
             .await
-            .map_err(|err| anyhow!(err))
     }
 
-    pub async fn send_message(&self, message: &SendMessage) -> Result<Message> {
-        self.client
-            .post(self.api_path.to_owned() + "/sendMessage")
-            .json(&message)
-            .send()
-            .await?
-            .json::<Response<Message>>()
+    pub async fn send_photo(&self, photo: &SendPhoto) -> Result<Message, TelegramError> {
+        self.request_with_upload("sendPhoto", photo, &[(&photo.photo, &photo.headers)])
             .await
-            .map(|resp| match resp {
-                Response::Ok { result } => Ok(result),
-                Response::Err { description, .. } => Err(anyhow!(description)),
-            })?
     }
 
-    pub async fn send_video(&self, video: &SendVideo) -> Result<Message> {
-        self.client
-            .post(self.api_path.to_owned() + "/sendVideo")
-            .json(&video)
-            .send()
-            .await?
-            .json::<Response<Message>>()
+    pub async fn send_audio(&self, audio: &SendAudio) -> Result<Message, TelegramError> {
+        self.request_with_upload("sendAudio", audio, &[(&audio.audio, &audio.headers)])
             .await
-            .map(|resp| match resp {
-                Response::Ok { result } => Ok(result),
-                Response::Err { description, .. } => Err(anyhow!(description)),
-            })?
     }
 
-    pub async fn edit_message_text(&self, edit_message_text: &EditMessageText) -> Result<Message> {
-        self.client
-            .post(self.api_path.to_owned() + "/editMessageText")
-            .json(&edit_message_text)
-            .send()
-            .await?
-            .json::<Response<Message>>()
+    pub async fn send_media_group(
+        &self,
+        media_group: &SendMediaGroup,
+    ) -> Result<Vec<Message>, TelegramError> {
+        let headers: Vec<_> = media_group
+            .media
+            .iter()
+            .map(|it| (it.media(), it.headers()))
+            .collect();
+        self.request_with_upload("sendMediaGroup", media_group, &headers)
             .await
-            .map(|resp| match resp {
-                Response::Ok { result } => Ok(result),
-                Response::Err { description, .. } => Err(anyhow!(description)),
-            })?
     }
 
-    pub async fn delete_message(&self, delete_message: &DeleteMessage) -> Result<()> {
-        self.client
-            .post(self.api_path.to_owned() + "/deleteMessage")
-            .json(&delete_message)
+    /// Sends `media` as albums of up to [`MEDIA_GROUP_LIMIT`] items.
+    ///
+    /// Audio cannot be mixed with photos and videos in one album, so it is
+    /// sent separately. A chunk consisting of a single item is sent with the
+    /// corresponding `send*` method. `caption` is attached to the first item.
+    /// Returned messages follow the order of the sent items.
+    pub async fn send_album(
+        &self,
+        chat_id: i64,
+        reply_to_message_id: Option<isize>,
+        caption: Option<String>,
+        media: Vec<InputMedia>,
+    ) -> Result<Vec<Message>, TelegramError> {
+        let (audio, mut visual): (Vec<_>, Vec<_>) = media
+            .into_iter()
+            .partition(|it| matches!(it, InputMedia::Audio { .. }));
+        visual.extend(audio);
+
+        let mut caption = caption;
+        let mut messages = vec![];
+        let mut rest = visual.as_slice();
+        while !rest.is_empty() {
+            let is_audio = matches!(rest[0], InputMedia::Audio { .. });
+            let len = rest
+                .iter()
+                .take(MEDIA_GROUP_LIMIT)
+                .take_while(|it| matches!(it, InputMedia::Audio { .. }) == is_audio)
+                .count();
+            let (chunk, tail) = rest.split_at(len);
+            rest = tail;
+
+            let mut chunk = chunk.to_vec();
+            chunk[0].set_caption(caption.take());
+
+            if chunk.len() > 1 {
+                let sent = self
+                    .send_media_group(&SendMediaGroup {
+                        chat_id,
+                        media: chunk,
+                        reply_to_message_id,
+                    })
+                    .await?;
+                messages.extend(sent);
+                continue;
+            }
+
+            let message = match chunk.remove(0) {
+                InputMedia::Video {
+                    media,
+                    caption,
+                    width,
+                    height,
+                    duration,
+                    headers,
+                } => {
+                    self.send_video(&SendVideo {
+                        chat_id,
+                        video: media,
+                        reply_to_message_id,
+                        caption,
+                        width,
+                        height,
+                        duration,
+                        headers,
+                    })
+                    .await?
+                }
+                InputMedia::Photo {
+                    media,
+                    caption,
+                    headers,
+                } => {
+                    self.send_photo(&SendPhoto {
+                        chat_id,
+                        photo: media,
+                        reply_to_message_id,
+                        caption,
+                        headers,
+                    })
+                    .await?
+                }
+                InputMedia::Audio {
+                    media,
+                    caption,
+                    headers,
+                } => {
+                    self.send_audio(&SendAudio {
+                        chat_id,
+                        audio: media,
+                        reply_to_message_id,
+                        caption,
+                        headers,
+                    })
+                    .await?
+                }
+            };
+            messages.push(message);
+        }
+
+        Ok(messages)
+    }
+
+    pub async fn edit_message_text(
+        &self,
+        edit_message_text: &EditMessageText,
+    ) -> Result<Message, TelegramError> {
+        self.request("editMessageText", edit_message_text).await
+    }
+
+    pub async fn edit_message_reply_markup(
+        &self,
+        edit_message_reply_markup: &EditMessageReplyMarkup,
+    ) -> Result<Message, TelegramError> {
+        self.request("editMessageReplyMarkup", edit_message_reply_markup)
+            .await
+    }
+
+    pub async fn delete_message(
+        &self,
+        delete_message: &DeleteMessage,
+    ) -> Result<bool, TelegramError> {
+        self.request("deleteMessage", delete_message).await
+    }
+
+    pub async fn answer_inline_query(
+        &self,
+        answer: &AnswerInlineQuery,
+    ) -> Result<bool, TelegramError> {
+        self.request("answerInlineQuery", answer).await
+    }
+
+    pub async fn answer_callback_query(
+        &self,
+        answer: &AnswerCallbackQuery,
+    ) -> Result<bool, TelegramError> {
+        self.request("answerCallbackQuery", answer).await
+    }
+
+    /// Calls `method` of Bot API. Requests limited by Telegram are repeated
+    /// after `retry_after` while the total wait fits into [`MAX_RETRY_WAIT`],
+    /// requests to migrated groups are repeated with the new supergroup ID
+    /// and without a reply.
+    async fn request<B: Serialize, T: DeserializeOwned>(
+        &self,
+        method: &str,
+        body: &B,
+    ) -> Result<T, TelegramError> {
+        let body = serde_json::to_value(body).map_err(TelegramError::Decode)?;
+        self.request_with_files(method, body, None).await
+    }
+
+    /// Calls `method` with URLs of files. When Telegram cannot fetch a URL
+    /// itself, the files are downloaded by the worker and uploaded instead
+    /// if they fit into [`UPLOAD_LIMIT`]. URLs listed in `headers` with
+    /// non-empty headers are always uploaded, Telegram cannot send them.
+    async fn request_with_upload<B: Serialize, T: DeserializeOwned>(
+        &self,
+        method: &str,
+        body: &B,
+        headers: &[(&str, &[(String, String)])],
+    ) -> Result<T, TelegramError> {
+        let mut body = serde_json::to_value(body).map_err(TelegramError::Decode)?;
+        let error = if headers.iter().any(|(_, headers)| !headers.is_empty()) {
+            None
+        } else {
+            match self.request_with_files(method, body.clone(), None).await {
+                Err(error) if error.is_url_rejected() => Some(error),
+                result => return result,
+            }
+        };
+
+        let files = match self.download_files(&mut body, headers).await {
+            Ok(files) if files.count > 0 => files,
+            Ok(_) => match error {
+                Some(error) => return Err(error),
+                None => return self.request_with_files(method, body, None).await,
+            },
+            Err(err) if err.is_file_too_large() => return Err(err),
+            Err(err) => {
+                console_error!("Cannot upload files to `{method}`: {err}");
+                return Err(error.unwrap_or(err));
+            }
+        };
+        console_log!("Uploading {} files to `{method}`", files.count);
+        self.request_with_files(method, body, Some(files)).await
+    }
+
+    /// Downloads URLs of `video`, `photo`, `audio` fields and `media` items
+    /// of `body` with their `headers`, replacing them with references to the
+    /// returned files. Stops as soon as the files exceed [`UPLOAD_LIMIT`].
+    async fn download_files(
+        &self,
+        body: &mut serde_json::Value,
+        headers: &[(&str, &[(String, String)])],
+    ) -> Result<FileParts, TelegramError> {
+        let get_headers = |url: &str| {
+            headers
+                .iter()
+                .find(|(it, _)| *it == url)
+                .map(|(_, headers)| *headers)
+                .unwrap_or_default()
+        };
+        let mut files = FileParts::default();
+        if let Some(fields) = body.as_object_mut() {
+            for name in ["video", "photo", "audio"] {
+                if let Some(url) = fields.get(name).and_then(|it| it.as_str()) {
+                    if url.starts_with("http") {
+                        self.download_file(&mut files, name, url, get_headers(url))
+                            .await?;
+                        fields.remove(name);
+                    }
+                }
+            }
+        }
+        if let Some(items) = body.get_mut("media").and_then(|it| it.as_array_mut()) {
+            for (idx, item) in items.iter_mut().enumerate() {
+                if let Some(url) = item.get("media").and_then(|it| it.as_str()) {
+                    if url.starts_with("http") {
+                        let name = format!("file{idx}");
+                        self.download_file(&mut files, &name, url, get_headers(url))
+                            .await?;
+                        item["media"] = format!("attach://{name}").into();
+                    }
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Downloads `url` straight into `files` as long as it fits into
+    /// [`UPLOAD_LIMIT`] together with the files downloaded before.
+    async fn download_file(
+        &self,
+        files: &mut FileParts,
+        name: &str,
+        url: &str,
+        headers: &[(String, String)],
+    ) -> Result<(), TelegramError> {
+        let mut request = self.client.get(url);
+        if !headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("User-Agent"))
+        {
+            request = request.header("User-Agent", USER_AGENT);
+        }
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request
             .send()
             .await
-            .map(|_| ())
-            .map_err(|err| anyhow!(err))
+            .and_then(|it| it.error_for_status())
+            .map_err(TelegramError::Http)?;
+        let content_length = response.content_length().unwrap_or_default() as usize;
+        if files.size + content_length > UPLOAD_LIMIT {
+            return Err(TelegramError::TooLarge(files.size + content_length));
+        }
+
+        let file_name = Url::parse(url)
+            .ok()
+            .and_then(|url| url.path_segments()?.next_back().map(|it| it.to_string()))
+            .filter(|it| it.contains('.'))
+            .unwrap_or(name.to_string());
+        files.content.reserve(content_length);
+        files.content.extend(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{file_name}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+
+        // `Content-Length` may be missing or wrong, so the body is counted
+        // while it is read.
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(TelegramError::Http)?;
+            files.size += chunk.len();
+            if files.size > UPLOAD_LIMIT {
+                return Err(TelegramError::TooLarge(files.size));
+            }
+            files.content.extend_from_slice(&chunk);
+        }
+        files.content.extend(b"\r\n");
+        files.count += 1;
+
+        Ok(())
+    }
+
+    /// Sends `body` as JSON or, when there are `files`, as `multipart/form-data`.
+    /// The multipart content is built once and shared by every attempt.
+    async fn request_with_files<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        mut body: serde_json::Value,
+        files: Option<FileParts>,
+    ) -> Result<T, TelegramError> {
+        let mut multipart = files.map(|files| Multipart::new(files, &body));
+        let mut waited = Duration::ZERO;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let request = self.client.post(format!("{}/{method}", self.api_path));
+            let request = match &multipart {
+                Some(multipart) => request
+                    .header(
+                        "Content-Type",
+                        format!("multipart/form-data; boundary={BOUNDARY}"),
+                    )
+                    .body(multipart.content.clone()),
+                None => request.json(&body),
+            };
+            let text = request
+                .send()
+                .await
+                .map_err(TelegramError::Http)?
+                .text()
+                .await
+                .map_err(TelegramError::Http)?;
+            let error = match serde_json::from_str(&text).map_err(TelegramError::Decode)? {
+                Response::Ok { result } => return Ok(result),
+                Response::Err {
+                    error_code,
+                    description,
+                    parameters,
+                } => TelegramError::Api {
+                    error_code,
+                    description,
+                    parameters: parameters.unwrap_or_default(),
+                },
+            };
+
+            if attempt == MAX_ATTEMPTS {
+                return Err(error);
+            }
+            if let Some(retry_after) = error.retry_after() {
+                if waited + retry_after > MAX_RETRY_WAIT {
+                    return Err(error);
+                }
+                console_log!("`{method}` is rate limited, retrying in {retry_after:?}");
+                Delay::from(retry_after).await;
+                waited += retry_after;
+            } else if let (Some(migrate_to_chat_id), true) =
+                (error.migrate_to_chat_id(), body.get("chat_id").is_some())
+            {
+                console_log!("Chat migrated to {migrate_to_chat_id}, retrying `{method}`");
+                body["chat_id"] = migrate_to_chat_id.into();
+                // Message IDs of the old group do not exist in the supergroup.
+                if let Some(fields) = body.as_object_mut() {
+                    fields.remove("reply_to_message_id");
+                }
+                if let Some(multipart) = &mut multipart {
+                    multipart.set_fields(&body);
+                }
+            } else {
+                return Err(error);
+            }
+        }
+    }
+}
+
+/// Parts of files uploaded in `multipart/form-data` request. Files are
+/// downloaded straight into them, so they are held in memory only once.
+#[derive(Default)]
+struct FileParts {
+    content: Vec<u8>,
+    /// Number of files.
+    count: usize,
+    /// Bytes of the files themselves, without part headers.
+    size: usize,
+}
+
+/// `multipart/form-data` content of an upload. Files come first, so fields
+/// can be replaced without downloading the files again.
+struct Multipart {
+    content: Bytes,
+    /// Length of the file parts at the start of `content`.
+    files_len: usize,
+}
+
+impl Multipart {
+    /// Appends fields of `body` to `files` without copying them.
+    fn new(files: FileParts, body: &serde_json::Value) -> Self {
+        let files_len = files.content.len();
+        let mut content = files.content;
+        content.extend(get_multipart_fields(body));
+        Self {
+            content: content.into(),
+            files_len,
+        }
+    }
+
+    /// Replaces fields with ones of `body`. Copies the files, it is only
+    /// needed when a request is repeated with another body, e.g. after a
+    /// chat migration.
+    fn set_fields(&mut self, body: &serde_json::Value) {
+        let mut content = self.content[..self.files_len].to_vec();
+        content.extend(get_multipart_fields(body));
+        self.content = content.into();
+    }
+}
+
+/// Parts of non-null fields of `body` followed by the closing boundary.
+fn get_multipart_fields(body: &serde_json::Value) -> Vec<u8> {
+    let mut content = vec![];
+    for (name, value) in body.as_object().into_iter().flatten() {
+        let value = match value {
+            serde_json::Value::Null => continue,
+            serde_json::Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        content.extend(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    content.extend(format!("--{BOUNDARY}--\r\n").as_bytes());
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(error_code: i64, description: &str) -> TelegramError {
+        TelegramError::Api {
+            error_code,
+            description: description.to_string(),
+            parameters: ResponseParameters::default(),
+        }
+    }
+
+    /// Names of helpers classifying `error`.
+    fn classify(error: &TelegramError) -> Vec<&'static str> {
+        [
+            ("file_too_large", error.is_file_too_large()),
+            ("url_rejected", error.is_url_rejected()),
+            ("invalid_file_id", error.is_invalid_file_id()),
+            ("message_not_modified", error.is_message_not_modified()),
+            ("bot_blocked", error.is_bot_blocked()),
+            ("chat_not_found", error.is_chat_not_found()),
+        ]
+        .into_iter()
+        .filter_map(|(name, matches)| matches.then_some(name))
+        .collect()
+    }
+
+    #[test]
+    fn keeps_files_when_multipart_fields_are_replaced() {
+        let files = FileParts {
+            content: b"<files>".to_vec(),
+            count: 1,
+            size: 7,
+        };
+        let mut multipart = Multipart::new(
+            files,
+            &serde_json::json!({ "chat_id": 1, "caption": "Cats", "reply_to_message_id": null }),
+        );
+        let content = multipart.content.clone();
+
+        multipart.set_fields(&serde_json::json!({ "chat_id": 2, "caption": "Cats" }));
+
+        let field = |name: &str, value: &str| {
+            format!("--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n")
+        };
+        let closing = format!("--{BOUNDARY}--\r\n");
+        assert_eq!(
+            content,
+            format!(
+                "<files>{}{}{closing}",
+                field("caption", "Cats"),
+                field("chat_id", "1")
+            )
+        );
+        assert_eq!(
+            multipart.content,
+            format!(
+                "<files>{}{}{closing}",
+                field("caption", "Cats"),
+                field("chat_id", "2")
+            )
+        );
+    }
+
+    #[test]
+    fn accepts_any_token_when_none_is_expected() {
+        assert!(verify_secret_token(None, None));
+        assert!(verify_secret_token(None, Some("anything")));
+    }
+
+    #[test]
+    fn verifies_secret_token() {
+        let expected = Some("s3cr3t_T0ken-value");
+        assert!(verify_secret_token(expected, Some("s3cr3t_T0ken-value")));
+        // Same length, one byte differs.
+        assert!(!verify_secret_token(expected, Some("s3cr3t_T0ken-valuf")));
+        assert!(!verify_secret_token(expected, Some("S3cr3t_T0ken-value")));
+        // Missing header.
+        assert!(!verify_secret_token(expected, None));
+        // Different lengths.
+        assert!(!verify_secret_token(expected, Some("")));
+        assert!(!verify_secret_token(expected, Some("s3cr3t_T0ken")));
+        assert!(!verify_secret_token(expected, Some("s3cr3t_T0ken-value2")));
+    }
+
+    #[test]
+    fn classifies_api_errors() {
+        let cases: &[(TelegramError, &[&str])] = &[
+            (
+                // Sent for both unknown file IDs and URLs Telegram cannot fetch.
+                api_error(400, "Bad Request: wrong file identifier/HTTP URL specified"),
+                &["url_rejected"],
+            ),
+            (
+                api_error(400, "Bad Request: failed to get HTTP URL content"),
+                &["url_rejected"],
+            ),
+            (
+                api_error(400, "Bad Request: wrong type of the web page content"),
+                &["url_rejected"],
+            ),
+            (
+                api_error(400, "Bad Request: file is too big"),
+                &["file_too_large"],
+            ),
+            (
+                api_error(413, "Request Entity Too Large"),
+                &["file_too_large"],
+            ),
+            (TelegramError::TooLarge(UPLOAD_LIMIT + 1), &["file_too_large"]),
+            (
+                api_error(400, "Bad Request: wrong remote file identifier specified: can't unserialize it"),
+                &["invalid_file_id"],
+            ),
+            (
+                api_error(400, "Bad Request: FILE_REFERENCE_EXPIRED"),
+                &["invalid_file_id"],
+            ),
+            (
+                api_error(400, "Bad Request: message is not modified: specified new message content and reply markup are exactly the same"),
+                &["message_not_modified"],
+            ),
+            (
+                api_error(403, "Forbidden: bot was blocked by the user"),
+                &["bot_blocked"],
+            ),
+            (
+                api_error(403, "Forbidden: bot was kicked from the group chat"),
+                &["bot_blocked"],
+            ),
+            (
+                api_error(400, "Bad Request: chat not found"),
+                &["chat_not_found"],
+            ),
+            (api_error(403, "Forbidden: chat not found"), &[]),
+            (api_error(500, "Internal Server Error"), &[]),
+        ];
+
+        for (error, expected) in cases {
+            assert_eq!(classify(error), *expected, "{error}");
+            // Helpers are disjoint, so the order callers check them in does not matter.
+            assert!(classify(error).len() <= 1, "{error}");
+        }
+    }
+
+    #[test]
+    fn reads_response_parameters() {
+        let error = TelegramError::Api {
+            error_code: 429,
+            description: "Too Many Requests: retry after 5".to_string(),
+            parameters: ResponseParameters {
+                retry_after: Some(5),
+                migrate_to_chat_id: None,
+            },
+        };
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(5)));
+        assert_eq!(error.migrate_to_chat_id(), None);
+        assert!(classify(&error).is_empty());
+
+        let error = TelegramError::Api {
+            error_code: 400,
+            description: "Bad Request: group chat was upgraded to a supergroup chat".to_string(),
+            parameters: ResponseParameters {
+                retry_after: None,
+                migrate_to_chat_id: Some(-1001234567890),
+            },
+        };
+        assert_eq!(error.retry_after(), None);
+        assert_eq!(error.migrate_to_chat_id(), Some(-1001234567890));
     }
 }
@@ -0,0 +1,307 @@
+use anyhow::{bail, Result};
+use url::Url;
+
+use super::{
+    http::{HttpClient, HttpRequest},
+    registry::find_route,
+};
+
+/// Short links expanded at most for a single link.
+const MAX_EXPANSIONS: usize = 3;
+
+/// Query params that only track who shared a link.
+const TRACKING_PARAMS: [&str; 14] = [
+    "igshid",
+    "igsh",
+    "si",
+    "feature",
+    "is_from_webapp",
+    "sender_device",
+    "sender_web_id",
+    "_t",
+    "_r",
+    "s",
+    "t",
+    "ref",
+    "ref_src",
+    "share_id",
+];
+
+/// Link reduced to the form used for resolution and caching.
+#[derive(Debug)]
+pub struct CanonicalUrl {
+    pub url: Url,
+    /// `<platform>:<content id>` when the content ID is known, the URL otherwise.
+    pub key: String,
+}
+
+/// Expands short links, then maps `url` to its canonical form.
+pub async fn canonicalize<C: HttpClient>(http_client: &C, url: Url) -> Result<CanonicalUrl> {
+    let url = expand_url(http_client, url).await?;
+    Ok(get_canonical_url(url))
+}
+
+/// Follows redirects of a short link to the page it points to. Short links
+/// may point to other short links, e.g. `t.co` to `vm.tiktok.com`, so up to
+/// [`MAX_EXPANSIONS`] of them are expanded, each within redirect limit of the
+/// HTTP client.
+async fn expand_url<C: HttpClient>(http_client: &C, url: Url) -> Result<Url> {
+    let mut expanded = url.clone();
+    for _ in 0..MAX_EXPANSIONS {
+        if !is_shortener(&expanded) {
+            return Ok(expanded);
+        }
+        // Status of the final page does not matter, only its URL is needed.
+        expanded = http_client
+            .send(HttpRequest::head(expanded.as_str()))
+            .await?
+            .url;
+    }
+
+    if is_shortener(&expanded) {
+        bail!("Cannot expand {url}, got {expanded}");
+    }
+    Ok(expanded)
+}
+
+fn is_shortener(url: &Url) -> bool {
+    find_route(url).is_some_and(|route| route.shortener)
+}
+
+/// Maps `url` to the canonical form of its route in [`super::registry::ROUTES`].
+fn get_canonical_url(url: Url) -> CanonicalUrl {
+    match find_route(&url).and_then(|route| route.get_canonical(&url)) {
+        Some((key, url)) => CanonicalUrl { url, key },
+        None => {
+            let url = strip_tracking_params(url);
+            CanonicalUrl {
+                key: url.to_string(),
+                url,
+            }
+        }
+    }
+}
+
+fn strip_tracking_params(mut url: Url) -> Url {
+    let query_pairs: Vec<_> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+
+    if query_pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query_pairs);
+    }
+
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    fn canonical(url: &str) -> (String, String) {
+        let CanonicalUrl { url, key } = get_canonical_url(Url::parse(url).unwrap());
+        (key, url.to_string())
+    }
+
+    #[test]
+    fn gets_platform_keys() {
+        let cases = [
+            (
+                "https://www.tiktok.com/@user/video/7300000000000000001?is_from_webapp=1",
+                "tiktok:7300000000000000001",
+                "https://www.tiktok.com/@user/video/7300000000000000001/",
+            ),
+            (
+                "https://www.tiktok.com/@user/photo/7300000000000000002",
+                "tiktok:7300000000000000002",
+                "https://www.tiktok.com/@user/photo/7300000000000000002/",
+            ),
+            (
+                "https://www.instagram.com/user/reels/CxYz123AbC/?igsh=abc",
+                "instagram:CxYz123AbC",
+                "https://www.instagram.com/reel/CxYz123AbC/",
+            ),
+            (
+                "https://instagr.am/p/CxYz123AbC",
+                "instagram:CxYz123AbC",
+                "https://www.instagram.com/p/CxYz123AbC/",
+            ),
+            (
+                "https://www.youtube.com/shorts/dQw4w9WgXcQ?feature=share",
+                "youtube:dQw4w9WgXcQ",
+                "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            ),
+            (
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42",
+                "youtube:dQw4w9WgXcQ",
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            ),
+            (
+                "https://www.youtube.com/live/jfKfPfyJRdk?si=abc",
+                "youtube:jfKfPfyJRdk",
+                "https://www.youtube.com/watch?v=jfKfPfyJRdk",
+            ),
+            (
+                "https://youtu.be/dQw4w9WgXcQ?si=abc",
+                "youtube:dQw4w9WgXcQ",
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            ),
+            (
+                "https://x.com/user/status/1700000000000000000?s=20",
+                "twitter:1700000000000000000",
+                "https://twitter.com/user/status/1700000000000000000/",
+            ),
+            (
+                "https://fxtwitter.com/user/status/1700000000000000000/photo/1",
+                "twitter:1700000000000000000",
+                "https://twitter.com/user/status/1700000000000000000/",
+            ),
+            (
+                "https://old.reddit.com/r/aww/comments/abc123/my_cat/",
+                "reddit:abc123",
+                "https://www.reddit.com/r/aww/comments/abc123/",
+            ),
+            (
+                "https://www.reddit.com/comments/abc123",
+                "reddit:abc123",
+                "https://www.reddit.com/comments/abc123/",
+            ),
+            (
+                "https://np.reddit.com/r/aww/comments/abc123/my_cat/",
+                "reddit:abc123",
+                "https://www.reddit.com/r/aww/comments/abc123/",
+            ),
+            (
+                "https://redd.it/abc123",
+                "reddit:abc123",
+                "https://www.reddit.com/comments/abc123/",
+            ),
+        ];
+        for (url, key, canonical_url) in cases {
+            assert_eq!(
+                canonical(url),
+                (key.to_string(), canonical_url.to_string()),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn normalizes_mobile_and_www_hosts() {
+        let urls = [
+            "https://tiktok.com/@user/video/7300000000000000001",
+            "https://www.tiktok.com/@user/video/7300000000000000001",
+            "https://m.tiktok.com/@user/video/7300000000000000001",
+        ];
+        for url in urls {
+            assert_eq!(canonical(url).0, "tiktok:7300000000000000001", "{url}");
+        }
+
+        let urls = [
+            "https://youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://m.youtube.com/watch?v=dQw4w9WgXcQ",
+        ];
+        for url in urls {
+            assert_eq!(canonical(url).0, "youtube:dQw4w9WgXcQ", "{url}");
+        }
+
+        let urls = [
+            "https://twitter.com/user/status/1700000000000000000",
+            "https://mobile.twitter.com/user/status/1700000000000000000",
+            "https://www.x.com/user/status/1700000000000000000",
+        ];
+        for url in urls {
+            assert_eq!(canonical(url).0, "twitter:1700000000000000000", "{url}");
+        }
+    }
+
+    #[test]
+    fn strips_tracking_params_of_unknown_links() {
+        assert_eq!(
+            canonical("https://www.instagram.com/stories/user/?utm_source=ig&igsh=abc&id=1"),
+            (
+                "https://www.instagram.com/stories/user/?id=1".to_string(),
+                "https://www.instagram.com/stories/user/?id=1".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn expands_short_links() {
+        let client = FakeHttpClient::new().redirect(
+            "https://vm.tiktok.com/ZMabc123/",
+            "https://www.tiktok.com/@user/video/7300000000000000001?_r=1",
+        );
+        let canonical_url = block_on(canonicalize(
+            &client,
+            Url::parse("https://vm.tiktok.com/ZMabc123/").unwrap(),
+        ))
+        .unwrap();
+
+        assert_eq!(canonical_url.key, "tiktok:7300000000000000001");
+        assert_eq!(client.requests.borrow()[0].method, reqwest::Method::HEAD);
+    }
+
+    #[test]
+    fn does_not_request_full_links() {
+        let client = FakeHttpClient::new();
+        let canonical_url = block_on(canonicalize(
+            &client,
+            Url::parse("https://x.com/user/status/1700000000000000000").unwrap(),
+        ))
+        .unwrap();
+
+        assert_eq!(canonical_url.key, "twitter:1700000000000000000");
+        assert!(client.requests.borrow().is_empty());
+    }
+
+    #[test]
+    fn expands_nested_short_links() {
+        let client = FakeHttpClient::new()
+            .redirect("https://t.co/AbCdEf1234", "https://vm.tiktok.com/ZMabc123/")
+            .redirect(
+                "https://vm.tiktok.com/ZMabc123/",
+                "https://www.tiktok.com/@user/video/7300000000000000001",
+            );
+        let canonical_url = block_on(canonicalize(
+            &client,
+            Url::parse("https://t.co/AbCdEf1234").unwrap(),
+        ))
+        .unwrap();
+
+        assert_eq!(canonical_url.key, "tiktok:7300000000000000001");
+        assert_eq!(client.requests.borrow().len(), 2);
+    }
+
+    #[test]
+    fn stops_expanding_short_link_loops() {
+        let client = FakeHttpClient::new()
+            .redirect("https://t.co/AbCdEf1234", "https://vm.tiktok.com/ZMabc123/")
+            .redirect("https://vm.tiktok.com/ZMabc123/", "https://t.co/AbCdEf1234");
+        let result = block_on(canonicalize(
+            &client,
+            Url::parse("https://t.co/AbCdEf1234").unwrap(),
+        ));
+
+        assert!(result.is_err());
+        assert_eq!(client.requests.borrow().len(), MAX_EXPANSIONS);
+    }
+
+    #[test]
+    fn fails_on_unexpanded_short_links() {
+        let client = FakeHttpClient::new().body("https://t.co/AbCdEf1234", 200, "");
+        let result = block_on(canonicalize(
+            &client,
+            Url::parse("https://t.co/AbCdEf1234").unwrap(),
+        ));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+use futures::future::{select, Either};
+use serde::{Deserialize, Serialize};
+use worker::Delay;
+#[cfg(not(test))]
+use worker::{console_error, console_log};
+// Console of the worker is not available to native test builds.
+#[cfg(test)]
+use std::{eprintln as console_error, println as console_log};
+
+use super::{
+    format::FormatPolicy, http::HttpClient, reddit::RedditUrlResolver, shorts::ShortsUrlResolver,
+    snap::SnapUrlResolver, syndication::SyndicationUrlResolver, tiktok::TikTokUrlResolver,
+    twitter::TwitterUrlResolver, youtube::YouTubeUrlResolver, Platform, ResolveUrl, ResolvedMedia,
+};
+
+/// Upstream used to resolve a link into media.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolver {
+    /// Page data embedded into TikTok HTML.
+    TikTok,
+    Snaptik,
+    Snapinsta,
+    Shortsmate,
+    /// Player response of YouTube innertube API.
+    YouTube,
+    /// JSON of embedded tweets.
+    Syndication,
+    SaveTwitter,
+    /// `.json` representation of Reddit posts.
+    Reddit,
+}
+
+impl FromStr for Resolver {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "tiktok" => Ok(Self::TikTok),
+            "snaptik" => Ok(Self::Snaptik),
+            "snapinsta" => Ok(Self::Snapinsta),
+            "shortsmate" => Ok(Self::Shortsmate),
+            "youtube" => Ok(Self::YouTube),
+            "syndication" => Ok(Self::Syndication),
+            "savetwitter" => Ok(Self::SaveTwitter),
+            "reddit" => Ok(Self::Reddit),
+            _ => bail!("Unknown resolver: {s}"),
+        }
+    }
+}
+
+impl Resolver {
+    pub const ALL: [Self; 8] = [
+        Self::TikTok,
+        Self::Snaptik,
+        Self::Snapinsta,
+        Self::Shortsmate,
+        Self::YouTube,
+        Self::Syndication,
+        Self::SaveTwitter,
+        Self::Reddit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TikTok => "tiktok",
+            Self::Snaptik => "snaptik",
+            Self::Snapinsta => "snapinsta",
+            Self::Shortsmate => "shortsmate",
+            Self::YouTube => "youtube",
+            Self::Syndication => "syndication",
+            Self::SaveTwitter => "savetwitter",
+            Self::Reddit => "reddit",
+        }
+    }
+
+    /// Platforms whose links the resolver accepts.
+    pub fn platforms(&self) -> &'static [Platform] {
+        match self {
+            Self::TikTok | Self::Snaptik => &[Platform::TikTok],
+            Self::Snapinsta => &[Platform::Instagram],
+            Self::Shortsmate => &[Platform::Shorts],
+            Self::YouTube => &[Platform::Shorts, Platform::YouTube],
+            Self::Syndication | Self::SaveTwitter => &[Platform::Twitter],
+            Self::Reddit => &[Platform::Reddit],
+        }
+    }
+
+    pub fn supports(&self, platform: &Platform) -> bool {
+        self.platforms().contains(platform)
+    }
+
+    /// Whether the resolver picks media by [`ResolverOptions::policy`].
+    pub fn uses_format_policy(&self) -> bool {
+        matches!(self, Self::Shortsmate | Self::YouTube)
+    }
+
+    pub fn default_chain(platform: &Platform) -> Vec<Self> {
+        match platform {
+            Platform::TikTok => vec![Self::TikTok, Self::Snaptik],
+            Platform::Instagram => vec![Self::Snapinsta],
+            Platform::Shorts => vec![Self::YouTube, Self::Shortsmate],
+            Platform::YouTube => vec![Self::YouTube],
+            Platform::Twitter => vec![Self::Syndication, Self::SaveTwitter],
+            Platform::Reddit => vec![Self::Reddit],
+        }
+    }
+
+    /// Creates the resolver implementation.
+    fn get_resolver<'a, C: HttpClient>(
+        &self,
+        http_client: &'a C,
+        options: &ResolverOptions,
+    ) -> Box<dyn ResolveUrl + 'a> {
+        match self {
+            Self::TikTok => Box::new(TikTokUrlResolver::new(http_client)),
+            Self::Snaptik => Box::new(SnapUrlResolver::new(http_client, &Platform::TikTok)),
+            Self::Snapinsta => Box::new(SnapUrlResolver::new(http_client, &Platform::Instagram)),
+            Self::Shortsmate => Box::new(ShortsUrlResolver::new(http_client, options.policy)),
+            Self::YouTube => Box::new(YouTubeUrlResolver::new(
+                http_client,
+                options.policy,
+                options.max_duration,
+            )),
+            Self::Syndication => Box::new(SyndicationUrlResolver::new(http_client)),
+            Self::SaveTwitter => Box::new(TwitterUrlResolver::new(http_client)),
+            Self::Reddit => Box::new(RedditUrlResolver::new(http_client)),
+        }
+    }
+}
+
+/// Settings shared by every resolver of a chain.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolverOptions {
+    /// Resolvers offering several formats pick one by it.
+    pub policy: FormatPolicy,
+    /// Longer videos are rejected by resolvers that know the duration upfront.
+    pub max_duration: u32,
+    /// Media needing headers is rejected and the next resolver is tried,
+    /// e.g. for inline results Telegram downloads by URL itself.
+    pub headerless: bool,
+}
+
+/// Errors of every resolver of a chain, in the order they were tried.
+#[derive(Debug)]
+pub struct ChainError {
+    pub errors: Vec<(Resolver, anyhow::Error)>,
+}
+
+impl std::fmt::Display for ChainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "All resolvers failed")?;
+        for (idx, (resolver, err)) in self.errors.iter().enumerate() {
+            let separator = if idx == 0 { ": " } else { "; " };
+            write!(f, "{separator}{}: {err}", resolver.name())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+/// Tries resolvers of `chain` in order, moving to the next one when the
+/// current fails or does not respond within `timeout`.
+pub async fn resolve_with_fallback<C: HttpClient>(
+    http_client: &C,
+    chain: &[Resolver],
+    url: &str,
+    timeout: Duration,
+    options: &ResolverOptions,
+) -> Result<Vec<ResolvedMedia>> {
+    let mut errors = vec![];
+    // Media rejected for its headers still describes the same post.
+    let mut rejected_media = None;
+
+    for resolver in chain {
+        match resolve_with_timeout(http_client, resolver, url, timeout, options).await {
+            Ok(media) if options.headerless && media.iter().any(|it| !it.headers.is_empty()) => {
+                console_log!(
+                    "`{}` resolved {url} into media needing headers",
+                    resolver.name()
+                );
+                errors.push((*resolver, anyhow!("Media needs headers")));
+                rejected_media = Some(media);
+            }
+            Ok(mut media) => {
+                console_log!("`{}` resolved {url}", resolver.name());
+                if let Some(rejected_media) = &rejected_media {
+                    fill_metadata(&mut media, rejected_media);
+                }
+                return Ok(media);
+            }
+            Err(err) => {
+                console_error!("`{}` failed to resolve {url}: {err}", resolver.name());
+                errors.push((*resolver, err));
+            }
+        }
+    }
+
+    Err(ChainError { errors }.into())
+}
+
+/// Copies descriptions missing from `media`, e.g. thumbnails of TikTok videos
+/// resolved by snaptik, from `other` items of the same kind at the same position.
+fn fill_metadata(media: &mut [ResolvedMedia], other: &[ResolvedMedia]) {
+    if media.len() != other.len() {
+        return;
+    }
+
+    for (it, other) in media.iter_mut().zip(other) {
+        if it.kind != other.kind {
+            continue;
+        }
+        it.title = it.title.take().or(other.title.clone());
+        it.author = it.author.take().or(other.author.clone());
+        it.thumbnail = it.thumbnail.take().or(other.thumbnail.clone());
+        it.duration = it.duration.or(other.duration);
+    }
+}
+
+/// Runs a single `resolver`, failing when it does not respond within `timeout`.
+pub async fn resolve_with_timeout<C: HttpClient>(
+    http_client: &C,
+    resolver: &Resolver,
+    url: &str,
+    timeout: Duration,
+    options: &ResolverOptions,
+) -> Result<Vec<ResolvedMedia>> {
+    let resolver = resolver.get_resolver(http_client, options);
+    let result = match select(resolver.resolve_url(url), Box::pin(Delay::from(timeout))).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(anyhow!("Timed out after {}ms", timeout.as_millis())),
+    };
+    result
+}
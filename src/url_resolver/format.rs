@@ -0,0 +1,210 @@
+use std::cmp::Reverse;
+
+use serde::Deserialize;
+
+use crate::telegram::{UPLOAD_LIMIT, URL_LIMIT};
+
+/// Rules picking one of the formats a video is offered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatPolicy {
+    /// Formats known to be larger are skipped.
+    pub max_size: u64,
+    /// Formats with higher resolution are skipped.
+    pub max_height: Option<u32>,
+}
+
+impl FormatPolicy {
+    /// The best quality Telegram can still receive.
+    pub const HIGH: Self = Self {
+        max_size: UPLOAD_LIMIT as u64,
+        max_height: None,
+    };
+
+    /// Small files Telegram downloads by URL without an upload.
+    pub const LOW: Self = Self {
+        max_size: URL_LIMIT as u64,
+        max_height: Some(480),
+    };
+}
+
+impl Default for FormatPolicy {
+    fn default() -> Self {
+        Self::HIGH
+    }
+}
+
+/// Single encoding of a video offered by a service.
+#[derive(Debug, Clone)]
+pub struct Format {
+    pub url: String,
+    /// Container, e.g. `mp4` or `webm`.
+    pub ext: Option<String>,
+    /// Video codec, e.g. `avc1.64001F`. `None` for audio-only formats.
+    pub video_codec: Option<String>,
+    pub has_audio: bool,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Size in bytes, exact or estimated.
+    pub size: Option<u64>,
+}
+
+impl Format {
+    /// Telegram plays H.264 in MP4 inline, other formats are sent as files
+    /// or not played at all.
+    pub fn is_playable(&self) -> bool {
+        self.has_audio
+            && self.ext.as_deref() == Some("mp4")
+            && self
+                .video_codec
+                .as_deref()
+                .is_some_and(|it| it.starts_with("avc1") || it.starts_with("h264"))
+    }
+
+    fn fits(&self, policy: &FormatPolicy) -> bool {
+        self.size.is_none_or(|size| size <= policy.max_size)
+            && match (self.height, policy.max_height) {
+                (Some(height), Some(max_height)) => height <= max_height,
+                _ => true,
+            }
+    }
+}
+
+/// Format as described by `yt-dlp`, `none` codecs mark video or audio only formats.
+#[derive(Debug, Deserialize)]
+pub struct YtDlpFormat {
+    url: Option<String>,
+    ext: Option<String>,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    filesize: Option<u64>,
+    filesize_approx: Option<u64>,
+}
+
+impl YtDlpFormat {
+    /// `None` for formats without a direct URL.
+    pub fn into_format(self) -> Option<Format> {
+        Some(Format {
+            url: self.url?,
+            ext: self.ext,
+            video_codec: self.vcodec.filter(|it| it != "none"),
+            has_audio: self.acodec.is_some_and(|it| it != "none"),
+            width: self.width,
+            height: self.height,
+            size: self.filesize.or(self.filesize_approx),
+        })
+    }
+}
+
+/// Orders video `formats` by preference of `policy`: H.264 MP4 with audio
+/// first, then any format with audio, then the rest, each group by resolution
+/// from the highest. Formats exceeding limits of `policy` go last, from the
+/// smallest, so there is something to try when nothing fits.
+pub fn sort_formats(formats: &[Format], policy: &FormatPolicy) -> Vec<Format> {
+    let (mut fitting, mut exceeding): (Vec<_>, Vec<_>) = formats
+        .iter()
+        .filter(|it| it.video_codec.is_some())
+        .cloned()
+        .partition(|it| it.fits(policy));
+
+    fitting.sort_by_key(|it| {
+        Reverse((
+            it.is_playable(),
+            it.has_audio,
+            it.height.unwrap_or_default(),
+            Reverse(it.size.unwrap_or_default()),
+        ))
+    });
+    exceeding.sort_by_key(|it| {
+        (
+            Reverse(it.is_playable()),
+            Reverse(it.has_audio),
+            it.height.unwrap_or_default(),
+        )
+    });
+
+    fitting.extend(exceeding);
+    fitting
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_formats(file: &str) -> Vec<Format> {
+        let path = format!("{}/tests/fixtures/{file}", env!("CARGO_MANIFEST_DIR"));
+        let json = std::fs::read_to_string(path).unwrap();
+        serde_json::from_str::<Vec<YtDlpFormat>>(&json)
+            .unwrap()
+            .into_iter()
+            .filter_map(YtDlpFormat::into_format)
+            .collect()
+    }
+
+    fn format_ids(formats: &[Format]) -> Vec<&str> {
+        formats
+            .iter()
+            .map(|it| it.url.rsplit('/').next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn prefers_playable_formats_with_high_policy() {
+        let formats = load_formats("formats/video.json");
+        let sorted = sort_formats(&formats, &FormatPolicy::HIGH);
+
+        // Video-only formats listed first are ranked after those with audio,
+        // VP9 with audio after H.264 of the same resolution, and the
+        // format over the size limit goes last.
+        assert_eq!(
+            format_ids(&sorted),
+            ["22", "135", "18", "302", "248", "137", "299"]
+        );
+    }
+
+    #[test]
+    fn keeps_formats_of_unknown_size() {
+        let formats = load_formats("formats/video.json");
+        let sorted = sort_formats(&formats, &FormatPolicy::HIGH);
+
+        assert_eq!(sorted[0].size, None);
+        assert!(sorted[0].fits(&FormatPolicy::HIGH));
+    }
+
+    #[test]
+    fn caps_height_and_size_with_low_policy() {
+        let formats = load_formats("formats/video.json");
+        let sorted = sort_formats(&formats, &FormatPolicy::LOW);
+
+        // Only `18` is within 480p and 20 MB, the rest are ordered from the
+        // smallest as fallbacks.
+        assert_eq!(
+            format_ids(&sorted),
+            ["18", "135", "22", "299", "302", "137", "248"]
+        );
+    }
+
+    #[test]
+    fn skips_audio_only_formats() {
+        let formats = load_formats("formats/video.json");
+        assert!(formats.iter().any(|it| it.video_codec.is_none()));
+
+        let sorted = sort_formats(&formats, &FormatPolicy::HIGH);
+        assert!(sorted.iter().all(|it| it.video_codec.is_some()));
+    }
+
+    #[test]
+    fn fits_low_policy_without_height() {
+        let format = Format {
+            url: "https://example.com/0".to_string(),
+            ext: Some("mp4".to_string()),
+            video_codec: Some("avc1.64001F".to_string()),
+            has_audio: true,
+            width: None,
+            height: None,
+            size: Some(1024),
+        };
+        assert!(format.fits(&FormatPolicy::LOW));
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{anyhow, bail, Result};
+use reqwest::{header::HeaderMap, Client, Method};
+use url::{form_urlencoded, Url};
+
+/// HTTP request issued by resolvers.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+impl HttpRequest {
+    pub fn get(url: &str) -> Self {
+        Self {
+            method: Method::GET,
+            url: url.to_string(),
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    pub fn head(url: &str) -> Self {
+        Self {
+            method: Method::HEAD,
+            ..Self::get(url)
+        }
+    }
+
+    pub fn post(url: &str) -> Self {
+        Self {
+            method: Method::POST,
+            ..Self::get(url)
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: String) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Sets `application/json` body.
+    pub fn json(self, body: &serde_json::Value) -> Self {
+        self.header("Content-Type", "application/json")
+            .body(body.to_string())
+    }
+
+    /// Sets `application/x-www-form-urlencoded` body.
+    pub fn form(self, fields: &[(&str, &str)]) -> Self {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(fields)
+            .finish();
+        self.header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+    }
+}
+
+/// HTTP response with the body read as text.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    /// Final URL after redirects.
+    pub url: Url,
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Fails on `4xx` and `5xx` responses, so pages of rate limits and
+    /// blocks are not parsed as regular markup.
+    pub fn error_for_status(self) -> Result<Self> {
+        if self.status >= 400 {
+            bail!("HTTP {} from {}", self.status, self.url);
+        }
+        Ok(self)
+    }
+}
+
+/// Transport used by resolvers, so they can be run against recorded responses.
+pub trait HttpClient {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse>;
+}
+
+impl HttpClient for Client {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let mut builder = self.request(request.method, &request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+
+        let response = builder.send().await?;
+        let url = response.url().clone();
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = response.text().await.map_err(|err| anyhow!(err))?;
+
+        Ok(HttpResponse {
+            url,
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::cell::RefCell;
+
+    use anyhow::{anyhow, Result};
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+    use url::Url;
+
+    use super::{HttpClient, HttpRequest, HttpResponse};
+
+    /// Response served for requests to URLs starting with `prefix`.
+    struct Route {
+        prefix: String,
+        url: Option<Url>,
+        status: u16,
+        headers: HeaderMap,
+        body: String,
+    }
+
+    /// Client serving files of `tests/fixtures/` instead of real requests.
+    #[derive(Default)]
+    pub struct FakeHttpClient {
+        routes: Vec<Route>,
+        pub requests: RefCell<Vec<HttpRequest>>,
+    }
+
+    impl FakeHttpClient {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Serves `tests/fixtures/<file>` for URLs starting with `prefix`.
+        pub fn fixture(self, prefix: &str, file: &str) -> Self {
+            let path = format!("{}/tests/fixtures/{file}", env!("CARGO_MANIFEST_DIR"));
+            let body = std::fs::read_to_string(&path)
+                .unwrap_or_else(|err| panic!("Cannot read {path}: {err}"));
+            self.body(prefix, 200, &body)
+        }
+
+        /// Serves `body` with `status` for URLs starting with `prefix`.
+        pub fn body(mut self, prefix: &str, status: u16, body: &str) -> Self {
+            self.routes.push(Route {
+                prefix: prefix.to_string(),
+                url: None,
+                status,
+                headers: HeaderMap::new(),
+                body: body.to_string(),
+            });
+            self
+        }
+
+        /// Answers URLs starting with `prefix` as if they redirected to `location`.
+        pub fn redirect(mut self, prefix: &str, location: &str) -> Self {
+            self = self.body(prefix, 200, "");
+            self.routes.last_mut().unwrap().url = Some(Url::parse(location).unwrap());
+            self
+        }
+
+        /// Adds a header to the last registered response.
+        pub fn header(mut self, name: &str, value: &str) -> Self {
+            let route = self
+                .routes
+                .last_mut()
+                .expect("No response to add header to");
+            route.headers.append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+            self
+        }
+    }
+
+    impl HttpClient for FakeHttpClient {
+        async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+            let route = self
+                .routes
+                .iter()
+                .find(|it| request.url.starts_with(&it.prefix))
+                .ok_or(anyhow!("No fixture for {}", request.url))?;
+            let response = HttpResponse {
+                url: match &route.url {
+                    Some(url) => url.clone(),
+                    None => Url::parse(&request.url)?,
+                },
+                status: route.status,
+                headers: route.headers.clone(),
+                body: route.body.clone(),
+            };
+            self.requests.borrow_mut().push(request);
+            Ok(response)
+        }
+    }
+}
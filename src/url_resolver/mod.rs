@@ -1,35 +1,179 @@
+pub mod canonical;
+pub mod chain;
+pub mod format;
+pub mod http;
+pub mod reddit;
+pub mod registry;
 pub mod shorts;
 pub mod snap;
+pub mod syndication;
+pub mod tiktok;
 pub mod twitter;
+pub mod youtube;
 
-use anyhow::{anyhow, bail, Result};
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
-#[derive(Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Platform {
     TikTok,
     Instagram,
     Shorts,
+    /// Regular videos and ended streams, Shorts are a separate platform.
+    YouTube,
     Twitter,
+    Reddit,
+}
+
+impl FromStr for Platform {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "tiktok" => Ok(Self::TikTok),
+            "instagram" => Ok(Self::Instagram),
+            "shorts" => Ok(Self::Shorts),
+            "youtube" => Ok(Self::YouTube),
+            "twitter" => Ok(Self::Twitter),
+            "reddit" => Ok(Self::Reddit),
+            _ => bail!("Unknown platform: {s}"),
+        }
+    }
 }
 
 impl Platform {
+    pub const ALL: [Self; 6] = [
+        Self::TikTok,
+        Self::Instagram,
+        Self::Shorts,
+        Self::YouTube,
+        Self::Twitter,
+        Self::Reddit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TikTok => "TikTok",
+            Self::Instagram => "Instagram",
+            Self::Shorts => "Shorts",
+            Self::YouTube => "YouTube",
+            Self::Twitter => "Twitter",
+            Self::Reddit => "Reddit",
+        }
+    }
+
+    /// Finds the platform of `url` in [`registry::ROUTES`].
     pub fn new(url: &Url) -> Result<Self> {
-        let host = url.host_str().ok_or(anyhow!("Cannot get URL host"))?;
-        if host.ends_with("tiktok.com") {
-            Ok(Self::TikTok)
-        } else if host.ends_with("instagram.com") {
-            Ok(Self::Instagram)
-        } else if host.ends_with("youtube.com") {
-            Ok(Self::Shorts)
-        } else if host.ends_with("twitter.com") || host == "x.com" {
-            Ok(Self::Twitter)
-        } else {
-            bail!("This kind of link is not supported yet.")
+        match registry::find_route(url) {
+            Some(route) => Ok(route.platform),
+            None => bail!("This kind of link is not supported yet."),
         }
     }
 }
 
-pub trait ResolveUrl<'a> {
-    async fn resolve_url(&self, url: &'a str) -> Result<Url>;
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Video,
+    Photo,
+    Audio,
+}
+
+impl MediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Video => "video",
+            Self::Photo => "photo",
+            Self::Audio => "audio",
+        }
+    }
+}
+
+/// Media a link is resolved into, along with everything known about it.
+#[derive(Debug, Clone)]
+pub struct ResolvedMedia {
+    pub kind: MediaKind,
+    /// Direct URL of the media.
+    pub url: Url,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Duration in seconds.
+    pub duration: Option<u32>,
+    /// Size in bytes.
+    pub size: Option<u64>,
+    pub author: Option<String>,
+    pub title: Option<String>,
+    pub thumbnail: Option<Url>,
+    /// Headers required to download `url`.
+    pub headers: Vec<(String, String)>,
+    /// Unix time in seconds after which `url` stops working.
+    pub expires_at: Option<u64>,
+    /// Separate audio track for a video-only `url`, e.g. DASH streams.
+    pub audio_url: Option<Url>,
+    /// Lower quality encodings of the same media, the best first.
+    pub variants: Vec<Variant>,
+}
+
+/// Alternative encoding of a media.
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub url: Url,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Size in bytes.
+    pub size: Option<u64>,
+}
+
+impl ResolvedMedia {
+    pub fn new(kind: MediaKind, url: Url) -> Self {
+        let expires_at = get_expires_at(&url);
+        Self {
+            kind,
+            url,
+            width: None,
+            height: None,
+            duration: None,
+            size: None,
+            author: None,
+            title: None,
+            thumbnail: None,
+            headers: vec![],
+            expires_at,
+            audio_url: None,
+            variants: vec![],
+        }
+    }
+
+    /// Returns the media with `variant` as its URL and without variants.
+    pub fn with_variant(&self, variant: &Variant) -> Self {
+        Self {
+            url: variant.url.clone(),
+            width: variant.width,
+            height: variant.height,
+            size: variant.size,
+            expires_at: get_expires_at(&variant.url),
+            variants: vec![],
+            ..self.clone()
+        }
+    }
+}
+
+/// Resolves a link into media. Object safe, so resolvers can be boxed.
+pub trait ResolveUrl {
+    fn resolve_url<'a>(&'a self, url: &'a str) -> LocalBoxFuture<'a, Result<Vec<ResolvedMedia>>>;
+}
+
+/// Reads expiry of signed CDN URLs: `x-expires` of TikTok, `expire` of
+/// YouTube and hex `oe` of Instagram.
+fn get_expires_at(url: &Url) -> Option<u64> {
+    url.query_pairs()
+        .find_map(|(key, value)| match key.as_ref() {
+            "x-expires" | "expire" => value.parse().ok(),
+            "oe" => u64::from_str_radix(&value, 16).ok(),
+            _ => None,
+        })
 }
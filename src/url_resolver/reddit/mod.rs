@@ -0,0 +1,358 @@
+use anyhow::{anyhow, bail, Result};
+use futures::future::LocalBoxFuture;
+use url::Url;
+
+use super::{
+    http::{HttpClient, HttpRequest},
+    MediaKind, ResolveUrl, ResolvedMedia, Variant,
+};
+
+mod util;
+use util::{get_post, unescape_url, Post, RedditVideo, DASH_AUDIO_REGEX, DASH_VIDEO_REGEX};
+
+static USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/119.0.0.0 Safari/537.36";
+
+/// Resolves Reddit posts using the `.json` representation of the post page.
+#[derive(Debug)]
+pub struct RedditUrlResolver<'a, C: HttpClient> {
+    http_client: &'a C,
+}
+
+impl<'a, C: HttpClient> RedditUrlResolver<'a, C> {
+    pub fn new(http_client: &'a C) -> Self {
+        Self { http_client }
+    }
+
+    /// Expands `v.redd.it`, `redd.it` and `/s/` share links to the post URL.
+    async fn get_post_url(&self, url: &str) -> Result<Url> {
+        let url = Url::parse(url)?;
+        if url.path().contains("/comments/") {
+            return Ok(url);
+        }
+
+        let res = self
+            .http_client
+            .send(HttpRequest::get(url.as_str()).header("User-Agent", USER_AGENT))
+            .await?
+            .error_for_status()?;
+        if !res.url.path().contains("/comments/") {
+            bail!("Cannot get post URL: {}", res.url);
+        }
+        Ok(res.url)
+    }
+
+    /// Returns the audio track and lower quality video tracks of the DASH playlist.
+    async fn get_dash_tracks(&self, video: &RedditVideo) -> Result<(Option<Url>, Vec<Variant>)> {
+        let dash_url = match &video.dash_url {
+            Some(dash_url) => Url::parse(&unescape_url(dash_url))?,
+            None => return Ok((None, vec![])),
+        };
+
+        let playlist = self
+            .http_client
+            .send(HttpRequest::get(dash_url.as_str()).header("User-Agent", USER_AGENT))
+            .await?
+            .error_for_status()?
+            .body;
+
+        let audio_url = match (video.is_gif, DASH_AUDIO_REGEX.captures(&playlist)) {
+            (Some(false) | None, Some(capts)) => Some(dash_url.join(capts[1].trim())?),
+            _ => None,
+        };
+
+        let mut variants = vec![];
+        for capts in DASH_VIDEO_REGEX.captures_iter(&playlist) {
+            let height: u32 = capts[1].parse()?;
+            if video.height.is_some_and(|max_height| height >= max_height) {
+                continue;
+            }
+            variants.push(Variant {
+                url: dash_url.join(capts[2].trim())?,
+                width: None,
+                height: Some(height),
+                size: None,
+            });
+        }
+        variants.sort_by_key(|it| std::cmp::Reverse(it.height));
+
+        Ok((audio_url, variants))
+    }
+
+    fn get_gallery(&self, post: &Post) -> Result<Vec<ResolvedMedia>> {
+        let items = post
+            .gallery_data
+            .as_ref()
+            .map(|it| it.items.as_slice())
+            .unwrap_or_default();
+        let metadata = post
+            .media_metadata
+            .as_ref()
+            .ok_or(anyhow!("Cannot get `media_metadata`"))?;
+
+        let mut media = vec![];
+        for item in items {
+            let source = match metadata.get(&item.media_id).and_then(|it| it.s.as_ref()) {
+                Some(source) => source,
+                None => continue,
+            };
+            let is_animated = metadata[&item.media_id].e.as_deref() == Some("AnimatedImage");
+            let (kind, url) = match (is_animated, &source.mp4, &source.u) {
+                (true, Some(mp4), _) => (MediaKind::Video, mp4),
+                (_, _, Some(u)) => (MediaKind::Photo, u),
+                _ => continue,
+            };
+
+            let mut it = ResolvedMedia::new(kind, Url::parse(&unescape_url(url))?);
+            it.width = source.x;
+            it.height = source.y;
+            it.author = post.author.clone();
+            it.title = post.title.clone();
+            media.push(it);
+        }
+
+        if media.is_empty() {
+            bail!("Gallery has no media");
+        }
+        Ok(media)
+    }
+
+    async fn resolve(&self, url: &str) -> Result<Vec<ResolvedMedia>> {
+        let mut json_url = self.get_post_url(url).await?;
+        let path = format!("{}.json", json_url.path().trim_end_matches('/'));
+        json_url.set_path(&path);
+        json_url.set_query(None);
+
+        let json = self
+            .http_client
+            .send(HttpRequest::get(json_url.as_str()).header("User-Agent", USER_AGENT))
+            .await?
+            .error_for_status()?
+            .body;
+        let post = get_post(&json)?;
+
+        if post.is_gallery == Some(true) {
+            return self.get_gallery(&post);
+        }
+
+        let video = post
+            .secure_media
+            .as_ref()
+            .and_then(|it| it.reddit_video.as_ref())
+            .or(post.media.as_ref().and_then(|it| it.reddit_video.as_ref()));
+        if let Some(video) = video {
+            let mut media = ResolvedMedia::new(
+                MediaKind::Video,
+                Url::parse(&unescape_url(&video.fallback_url))?,
+            );
+            media.width = video.width;
+            media.height = video.height;
+            media.duration = video.duration;
+            media.author = post.author.clone();
+            media.title = post.title.clone();
+            media.thumbnail = post
+                .thumbnail
+                .as_deref()
+                .filter(|it| it.starts_with("http"))
+                .and_then(|it| Url::parse(&unescape_url(it)).ok());
+            if let Ok((audio_url, variants)) = self.get_dash_tracks(video).await {
+                media.audio_url = audio_url;
+                media.variants = variants;
+            }
+            return Ok(vec![media]);
+        }
+
+        match (post.post_hint.as_deref(), &post.url) {
+            (Some("image"), Some(url)) => {
+                let mut media =
+                    ResolvedMedia::new(MediaKind::Photo, Url::parse(&unescape_url(url))?);
+                media.author = post.author.clone();
+                media.title = post.title.clone();
+                Ok(vec![media])
+            }
+            _ => bail!("Post has no media"),
+        }
+    }
+}
+
+impl<C: HttpClient> ResolveUrl for RedditUrlResolver<'_, C> {
+    fn resolve_url<'b>(&'b self, url: &'b str) -> LocalBoxFuture<'b, Result<Vec<ResolvedMedia>>> {
+        Box::pin(self.resolve(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    const POST_URL: &str = "https://www.reddit.com/r/aww/comments/abc123/my_cat/";
+    const JSON_URL: &str = "https://www.reddit.com/r/aww/comments/abc123/my_cat.json";
+    const DASH_URL: &str = "https://v.redd.it/abc123/DASHPlaylist.mpd";
+
+    fn resolve(client: &FakeHttpClient, url: &str) -> Result<Vec<ResolvedMedia>> {
+        block_on(RedditUrlResolver::new(client).resolve(url))
+    }
+
+    #[test]
+    fn resolves_video_with_dash_tracks() {
+        let client = FakeHttpClient::new()
+            .fixture(JSON_URL, "reddit/video.json")
+            .fixture(DASH_URL, "reddit/dash.mpd");
+        let media = resolve(&client, POST_URL).unwrap();
+
+        assert_eq!(media.len(), 1);
+        let video = &media[0];
+        assert_eq!(video.kind, MediaKind::Video);
+        assert_eq!(
+            video.url.as_str(),
+            "https://v.redd.it/abc123/DASH_720.mp4?source=fallback"
+        );
+        assert_eq!((video.width, video.height), (Some(720), Some(1280)));
+        assert_eq!(video.duration, Some(21));
+        assert_eq!(video.author.as_deref(), Some("catowner"));
+        assert_eq!(
+            video.thumbnail.as_ref().map(Url::as_str),
+            Some("https://b.thumbs.redditmedia.com/thumb.jpg?width=140&s=abc")
+        );
+        assert_eq!(
+            video.audio_url.as_ref().map(Url::as_str),
+            Some("https://v.redd.it/abc123/DASH_AUDIO_128.mp4")
+        );
+        let variants: Vec<_> = video.variants.iter().map(|it| it.url.path()).collect();
+        assert_eq!(variants, ["/abc123/DASH_480.mp4", "/abc123/DASH_360.mp4"]);
+
+        let requests = client.requests.borrow();
+        assert_eq!(requests[0].url, JSON_URL);
+        assert_eq!(
+            requests[1].url,
+            "https://v.redd.it/abc123/DASHPlaylist.mpd?a=1700000000&v=1"
+        );
+    }
+
+    #[test]
+    fn resolves_video_without_dash_playlist() {
+        let client = FakeHttpClient::new()
+            .fixture(JSON_URL, "reddit/video.json")
+            .body(DASH_URL, 403, "Forbidden");
+        let media = resolve(&client, POST_URL).unwrap();
+
+        assert!(media[0].audio_url.is_none());
+        assert!(media[0].variants.is_empty());
+    }
+
+    #[test]
+    fn expands_share_links() {
+        let client = FakeHttpClient::new()
+            .fixture(DASH_URL, "reddit/dash.mpd")
+            .redirect("https://v.redd.it/abc123", POST_URL)
+            .fixture(JSON_URL, "reddit/video.json");
+        let media = resolve(&client, "https://v.redd.it/abc123").unwrap();
+
+        assert_eq!(media[0].kind, MediaKind::Video);
+        assert_eq!(client.requests.borrow()[1].url, JSON_URL);
+    }
+
+    #[test]
+    fn fails_on_unexpanded_share_links() {
+        let client = FakeHttpClient::new().redirect(
+            "https://www.reddit.com/r/aww/s/AbCdEf",
+            "https://www.reddit.com/login/",
+        );
+        assert!(resolve(&client, "https://www.reddit.com/r/aww/s/AbCdEf").is_err());
+    }
+
+    /// Items follow `gallery_data`, items without metadata are skipped.
+    #[test]
+    fn resolves_gallery_in_order() {
+        let client = FakeHttpClient::new().fixture(
+            "https://www.reddit.com/r/pics/comments/def456/sunset.json",
+            "reddit/gallery.json",
+        );
+        let media = resolve(
+            &client,
+            "https://www.reddit.com/r/pics/comments/def456/sunset",
+        )
+        .unwrap();
+
+        let items: Vec<_> = media.iter().map(|it| (it.kind, it.url.as_str())).collect();
+        assert_eq!(
+            items,
+            [
+                (
+                    MediaKind::Photo,
+                    "https://preview.redd.it/img1.jpg?width=1920&format=pjpg&s=abc"
+                ),
+                (
+                    MediaKind::Video,
+                    "https://preview.redd.it/gif2.gif?format=mp4&s=def"
+                ),
+            ]
+        );
+        assert_eq!(media[0].author.as_deref(), Some("photographer"));
+    }
+
+    #[test]
+    fn resolves_image() {
+        let client = FakeHttpClient::new().fixture(
+            "https://www.reddit.com/r/pics/comments/ghi789/view.json",
+            "reddit/image.json",
+        );
+        let media = resolve(
+            &client,
+            "https://www.reddit.com/r/pics/comments/ghi789/view/",
+        )
+        .unwrap();
+
+        assert_eq!(media.len(), 1);
+        assert_eq!(media[0].kind, MediaKind::Photo);
+        assert_eq!(media[0].url.as_str(), "https://i.redd.it/ghi789.jpeg");
+    }
+
+    #[test]
+    fn resolves_original_post_of_crossposts() {
+        let client = FakeHttpClient::new()
+            .fixture(JSON_URL, "reddit/crosspost.json")
+            .fixture(DASH_URL, "reddit/dash.mpd");
+        let media = resolve(&client, POST_URL).unwrap();
+
+        assert_eq!(media.len(), 1);
+        assert_eq!(
+            media[0].url.as_str(),
+            "https://v.redd.it/abc123/DASH_720.mp4?source=fallback"
+        );
+        assert_eq!(media[0].author.as_deref(), Some("catowner"));
+        assert_eq!(
+            media[0].title.as_deref(),
+            Some("My cat learned to open doors")
+        );
+        assert!(media[0].audio_url.is_some());
+    }
+
+    #[test]
+    fn resolves_gif_without_audio_track() {
+        let client = FakeHttpClient::new()
+            .fixture(JSON_URL, "reddit/gif.json")
+            .fixture(DASH_URL, "reddit/dash.mpd");
+        let media = resolve(&client, POST_URL).unwrap();
+
+        // The playlist lists an audio track, but GIFs are silent.
+        assert!(media[0].audio_url.is_none());
+        assert_eq!(media[0].variants.len(), 2);
+    }
+
+    #[test]
+    fn rejects_text_posts() {
+        let client = FakeHttpClient::new().fixture(
+            "https://www.reddit.com/r/books/comments/jkl012/question.json",
+            "reddit/text.json",
+        );
+        let err = resolve(
+            &client,
+            "https://www.reddit.com/r/books/comments/jkl012/question/",
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Post has no media");
+    }
+}
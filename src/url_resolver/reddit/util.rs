@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+
+lazy_static! {
+    pub static ref DASH_AUDIO_REGEX: Regex = Regex::new(
+        r#"(?s)<AdaptationSet[^>]*(?:contentType="audio"|mimeType="audio/mp4")[^>]*>.*?<BaseURL>(.+?)</BaseURL>"#
+    )
+    .unwrap();
+    pub static ref DASH_VIDEO_REGEX: Regex = Regex::new(
+        r#"(?s)<Representation[^>]*?\sheight="(\d+)"[^>]*>\s*<BaseURL>(.+?)</BaseURL>"#
+    )
+    .unwrap();
+}
+
+#[derive(Debug, Deserialize)]
+struct Listing {
+    data: ListingData,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListingData {
+    children: Vec<Child>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Child {
+    data: Post,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Post {
+    pub author: Option<String>,
+    pub title: Option<String>,
+    /// URL, or `self`, `default`, `nsfw` and such for posts without one.
+    pub thumbnail: Option<String>,
+    pub url: Option<String>,
+    pub post_hint: Option<String>,
+    pub is_gallery: Option<bool>,
+    pub secure_media: Option<PostMedia>,
+    pub media: Option<PostMedia>,
+    pub gallery_data: Option<GalleryData>,
+    pub media_metadata: Option<HashMap<String, MediaMetadata>>,
+    pub crosspost_parent_list: Option<Vec<Post>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostMedia {
+    pub reddit_video: Option<RedditVideo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedditVideo {
+    pub fallback_url: String,
+    pub dash_url: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub duration: Option<u32>,
+    pub is_gif: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GalleryData {
+    pub items: Vec<GalleryItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GalleryItem {
+    pub media_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaMetadata {
+    /// `Image` or `AnimatedImage`.
+    pub e: Option<String>,
+    pub s: Option<MediaSource>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaSource {
+    pub u: Option<String>,
+    pub mp4: Option<String>,
+    pub x: Option<u32>,
+    pub y: Option<u32>,
+}
+
+/// Extracts the post from `.json` response, following crossposts to the original.
+pub fn get_post(json: &str) -> Result<Post> {
+    let listings: Vec<Listing> = serde_json::from_str(json)?;
+    let post = listings
+        .into_iter()
+        .next()
+        .and_then(|listing| listing.data.children.into_iter().next())
+        .map(|child| child.data)
+        .ok_or(anyhow!("Cannot get post"))?;
+
+    match post.crosspost_parent_list {
+        Some(mut parents) if !parents.is_empty() => Ok(parents.remove(0)),
+        _ => Ok(post),
+    }
+}
+
+/// Reddit escapes `&` in media URLs of the JSON API.
+pub fn unescape_url(url: &str) -> String {
+    url.replace("&amp;", "&")
+}
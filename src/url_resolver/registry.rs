@@ -0,0 +1,386 @@
+use url::Url;
+
+use super::Platform;
+
+/// Cache key and canonical URL of a link.
+type Canonical = (String, String);
+
+/// Links of a platform served by a set of hosts.
+#[derive(Debug)]
+pub struct Route {
+    pub platform: Platform,
+    /// Exact hosts, `*.` prefix matches any subdomain but not the domain itself.
+    pub hosts: &'static [&'static str],
+    /// Whether non-empty path segments are a link to media.
+    pub matches_path: fn(&[&str]) -> bool,
+    /// Short links point to media only through redirects.
+    pub shortener: bool,
+    /// Cache key and canonical URL of a link, `None` when the link does not
+    /// identify its content, e.g. short links.
+    pub canonical: fn(&Url, &[&str]) -> Option<Canonical>,
+}
+
+/// Every supported kind of link.
+pub static ROUTES: [Route; 14] = [
+    Route {
+        platform: Platform::TikTok,
+        hosts: &["tiktok.com", "www.tiktok.com", "m.tiktok.com"],
+        matches_path: |segments| matches!(segments, [_, "video" | "photo", _, ..]),
+        shortener: false,
+        canonical: |_, segments| match segments {
+            [user, kind, id, ..] => Some((
+                format!("tiktok:{id}"),
+                format!("https://www.tiktok.com/{user}/{kind}/{id}/"),
+            )),
+            _ => None,
+        },
+    },
+    Route {
+        platform: Platform::TikTok,
+        hosts: &["tiktok.com", "www.tiktok.com", "m.tiktok.com"],
+        matches_path: |segments| matches!(segments, ["t", _]),
+        shortener: true,
+        canonical: |_, _| None,
+    },
+    Route {
+        platform: Platform::TikTok,
+        hosts: &["vm.tiktok.com", "vt.tiktok.com"],
+        matches_path: |segments| matches!(segments, [_]),
+        shortener: true,
+        canonical: |_, _| None,
+    },
+    Route {
+        platform: Platform::Instagram,
+        hosts: &[
+            "instagram.com",
+            "www.instagram.com",
+            "m.instagram.com",
+            "instagr.am",
+        ],
+        matches_path: |segments| matches!(segments, [.., "p" | "reel" | "reels" | "tv", _]),
+        shortener: false,
+        canonical: |_, segments| match segments {
+            [.., kind, code] => {
+                let kind = if *kind == "reels" { "reel" } else { kind };
+                Some((
+                    format!("instagram:{code}"),
+                    format!("https://www.instagram.com/{kind}/{code}/"),
+                ))
+            }
+            _ => None,
+        },
+    },
+    Route {
+        platform: Platform::Shorts,
+        hosts: &["youtube.com", "www.youtube.com", "m.youtube.com"],
+        matches_path: |segments| matches!(segments, ["shorts", _]),
+        shortener: false,
+        canonical: |_, segments| match segments {
+            [_, id] => Some((
+                format!("youtube:{id}"),
+                format!("https://www.youtube.com/shorts/{id}"),
+            )),
+            _ => None,
+        },
+    },
+    Route {
+        platform: Platform::YouTube,
+        hosts: &["youtube.com", "www.youtube.com", "m.youtube.com"],
+        matches_path: |segments| matches!(segments, ["watch"]),
+        shortener: false,
+        canonical: |url, _| {
+            url.query_pairs()
+                .find(|(key, _)| key == "v")
+                .map(|(_, id)| get_youtube_canonical(&id))
+        },
+    },
+    Route {
+        platform: Platform::YouTube,
+        hosts: &["youtube.com", "www.youtube.com", "m.youtube.com"],
+        matches_path: |segments| matches!(segments, ["live", _]),
+        shortener: false,
+        canonical: |_, segments| segments.last().map(|id| get_youtube_canonical(id)),
+    },
+    Route {
+        platform: Platform::YouTube,
+        hosts: &["youtu.be"],
+        matches_path: |segments| matches!(segments, [_]),
+        shortener: false,
+        canonical: |_, segments| segments.last().map(|id| get_youtube_canonical(id)),
+    },
+    Route {
+        platform: Platform::Twitter,
+        hosts: &[
+            "twitter.com",
+            "www.twitter.com",
+            "mobile.twitter.com",
+            "x.com",
+            "www.x.com",
+            "fxtwitter.com",
+            "vxtwitter.com",
+            "fixupx.com",
+        ],
+        matches_path: |segments| matches!(segments, [_, "status", _, ..]),
+        shortener: false,
+        canonical: |_, segments| match segments {
+            [user, _, id, ..] => Some((
+                format!("twitter:{id}"),
+                format!("https://twitter.com/{user}/status/{id}/"),
+            )),
+            _ => None,
+        },
+    },
+    // Short links of Twitter may point anywhere, the platform is checked
+    // again once they are expanded.
+    Route {
+        platform: Platform::Twitter,
+        hosts: &["t.co"],
+        matches_path: |segments| matches!(segments, [_]),
+        shortener: true,
+        canonical: |_, _| None,
+    },
+    Route {
+        platform: Platform::Reddit,
+        hosts: &["reddit.com", "*.reddit.com"],
+        matches_path: |segments| {
+            segments
+                .iter()
+                .position(|it| *it == "comments")
+                .is_some_and(|idx| idx + 1 < segments.len())
+        },
+        shortener: false,
+        canonical: |_, segments| match segments {
+            [.., "comments", id] => Some((
+                format!("reddit:{id}"),
+                format!("https://www.reddit.com/comments/{id}/"),
+            )),
+            ["r", subreddit, "comments", id, ..] => Some((
+                format!("reddit:{id}"),
+                format!("https://www.reddit.com/r/{subreddit}/comments/{id}/"),
+            )),
+            _ => None,
+        },
+    },
+    Route {
+        platform: Platform::Reddit,
+        hosts: &["reddit.com", "*.reddit.com"],
+        matches_path: |segments| matches!(segments, ["r", _, "s", _]),
+        shortener: true,
+        canonical: |_, _| None,
+    },
+    Route {
+        platform: Platform::Reddit,
+        hosts: &["redd.it"],
+        matches_path: |segments| matches!(segments, [_]),
+        shortener: false,
+        canonical: |_, segments| match segments {
+            [id] => Some((
+                format!("reddit:{id}"),
+                format!("https://www.reddit.com/comments/{id}/"),
+            )),
+            _ => None,
+        },
+    },
+    Route {
+        platform: Platform::Reddit,
+        hosts: &["v.redd.it"],
+        matches_path: |segments| matches!(segments, [_]),
+        shortener: true,
+        canonical: |_, _| None,
+    },
+];
+
+/// Regular videos, streams and `youtu.be` links share the watch page.
+fn get_youtube_canonical(id: &str) -> Canonical {
+    (
+        format!("youtube:{id}"),
+        format!("https://www.youtube.com/watch?v={id}"),
+    )
+}
+
+impl Route {
+    fn matches_host(&self, host: &str) -> bool {
+        self.hosts
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|it| it.len() > 1 && it.ends_with('.')),
+                None => host == *pattern,
+            })
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default();
+        self.matches_host(host) && (self.matches_path)(&get_path_segments(url))
+    }
+
+    /// Cache key and canonical URL of `url` matching the route.
+    pub fn get_canonical(&self, url: &Url) -> Option<(String, Url)> {
+        let (key, canonical_url) = (self.canonical)(url, &get_path_segments(url))?;
+        Some((key, Url::parse(&canonical_url).ok()?))
+    }
+}
+
+fn get_path_segments(url: &Url) -> Vec<&str> {
+    url.path_segments()
+        .map(|it| it.filter(|it| !it.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Finds the route of `url`, if any.
+pub fn find_route(url: &Url) -> Option<&'static Route> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    ROUTES.iter().find(|route| route.matches(url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(url: &str) -> Option<Platform> {
+        find_route(&Url::parse(url).unwrap()).map(|it| it.platform)
+    }
+
+    #[test]
+    fn accepts_supported_links() {
+        let cases = [
+            (
+                "https://www.tiktok.com/@user/video/7300000000000000001",
+                Platform::TikTok,
+            ),
+            (
+                "https://m.tiktok.com/@user/photo/7300000000000000001",
+                Platform::TikTok,
+            ),
+            ("https://www.tiktok.com/t/ZTRabc123/", Platform::TikTok),
+            ("https://vm.tiktok.com/ZMabc123/", Platform::TikTok),
+            (
+                "https://www.instagram.com/p/CxYz123AbC/",
+                Platform::Instagram,
+            ),
+            (
+                "https://instagram.com/user/reel/CxYz123AbC",
+                Platform::Instagram,
+            ),
+            ("https://instagr.am/p/CxYz123AbC", Platform::Instagram),
+            (
+                "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+                Platform::Shorts,
+            ),
+            ("https://m.youtube.com/shorts/dQw4w9WgXcQ", Platform::Shorts),
+            (
+                "https://m.youtube.com/watch?v=dQw4w9WgXcQ",
+                Platform::YouTube,
+            ),
+            (
+                "https://www.youtube.com/live/jfKfPfyJRdk",
+                Platform::YouTube,
+            ),
+            ("https://youtu.be/dQw4w9WgXcQ", Platform::YouTube),
+            (
+                "https://twitter.com/user/status/1700000000000000000",
+                Platform::Twitter,
+            ),
+            (
+                "https://mobile.twitter.com/user/status/1700000000000000000",
+                Platform::Twitter,
+            ),
+            (
+                "https://x.com/user/status/1700000000000000000/photo/1",
+                Platform::Twitter,
+            ),
+            (
+                "https://fxtwitter.com/user/status/1700000000000000000",
+                Platform::Twitter,
+            ),
+            (
+                "https://www.reddit.com/r/aww/comments/abc123/my_cat/",
+                Platform::Reddit,
+            ),
+            (
+                "https://old.reddit.com/r/aww/comments/abc123",
+                Platform::Reddit,
+            ),
+            ("https://www.reddit.com/r/aww/s/AbCdEf", Platform::Reddit),
+            ("https://redd.it/abc123", Platform::Reddit),
+            ("https://v.redd.it/abc123", Platform::Reddit),
+            ("https://t.co/AbCdEf1234", Platform::Twitter),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(platform(url), Some(expected), "{url}");
+        }
+    }
+
+    #[test]
+    fn rejects_lookalike_hosts() {
+        let cases = [
+            "https://evil-tiktok.com/@user/video/7300000000000000001",
+            "https://tiktok.com.evil/@user/video/7300000000000000001",
+            "https://notyoutube.com/shorts/dQw4w9WgXcQ",
+            "https://youtube.com.evil/watch?v=dQw4w9WgXcQ",
+            "https://reddit.com.evil/r/aww/comments/abc123",
+            "https://evilreddit.com/r/aww/comments/abc123",
+            "https://nottwitter.com/user/status/1700000000000000000",
+        ];
+        for url in cases {
+            assert_eq!(platform(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_paths() {
+        let cases = [
+            "https://www.tiktok.com/@user",
+            "https://www.tiktok.com/@user/live",
+            "https://vm.tiktok.com/",
+            "https://www.instagram.com/user/",
+            "https://www.youtube.com/shorts/",
+            "https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw",
+            "https://youtu.be/",
+            "https://twitter.com/user",
+            "https://twitter.com/user/likes",
+            "https://www.reddit.com/r/aww/",
+            "https://www.reddit.com/r/aww/comments/",
+            "ftp://www.tiktok.com/@user/video/7300000000000000001",
+        ];
+        for url in cases {
+            assert_eq!(platform(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn matches_subdomain_patterns() {
+        let route = ROUTES
+            .iter()
+            .find(|it| it.hosts.contains(&"*.reddit.com"))
+            .unwrap();
+        assert!(route.matches_host("www.reddit.com"));
+        assert!(route.matches_host("np.old.reddit.com"));
+        assert!(!route.matches_host("xreddit.com"));
+        assert!(!route.matches_host(".reddit.com"));
+    }
+
+    #[test]
+    fn marks_short_links() {
+        let cases = [
+            ("https://vm.tiktok.com/ZMabc123/", true),
+            ("https://www.tiktok.com/t/ZTRabc123/", true),
+            ("https://t.co/AbCdEf1234", true),
+            ("https://www.reddit.com/r/aww/s/AbCdEf", true),
+            ("https://v.redd.it/abc123", true),
+            ("https://redd.it/abc123", false),
+            ("https://youtu.be/dQw4w9WgXcQ", false),
+        ];
+        for (url, expected) in cases {
+            let route = find_route(&Url::parse(url).unwrap()).unwrap();
+            assert_eq!(route.shortener, expected, "{url}");
+            // Short links do not identify their content until expanded.
+            if route.shortener {
+                assert!(route.get_canonical(&Url::parse(url).unwrap()).is_none());
+            }
+        }
+    }
+}
@@ -1,19 +1,24 @@
 use anyhow::{anyhow, bail, Result};
-use reqwest::Client;
+use futures::future::LocalBoxFuture;
 use url::Url;
 
-use crate::url_resolver::shorts::util::get_media_url;
+use crate::url_resolver::shorts::util::get_formats;
 
 use self::util::get_cookie;
 
-use super::ResolveUrl;
+use super::{
+    format::{sort_formats, FormatPolicy},
+    http::{HttpClient, HttpRequest},
+    MediaKind, ResolveUrl, ResolvedMedia, Variant,
+};
 
 mod util;
 use util::CSRF_REGEX;
 
 #[derive(Debug)]
-pub struct ShortsUrlResolver<'a> {
-    http_client: &'a Client,
+pub struct ShortsUrlResolver<'a, C: HttpClient> {
+    http_client: &'a C,
+    policy: FormatPolicy,
 }
 
 #[derive(Debug)]
@@ -22,9 +27,12 @@ struct AuthData {
     cookie: String,
 }
 
-impl<'a> ShortsUrlResolver<'a> {
-    pub fn new(http_client: &'a Client) -> Self {
-        Self { http_client }
+impl<'a, C: HttpClient> ShortsUrlResolver<'a, C> {
+    pub fn new(http_client: &'a C, policy: FormatPolicy) -> Self {
+        Self {
+            http_client,
+            policy,
+        }
     }
 
     fn get_csrf(&self, html: &str) -> Result<String> {
@@ -40,34 +48,149 @@ impl<'a> ShortsUrlResolver<'a> {
     async fn get_auth_data(&self) -> Result<AuthData> {
         let res = self
             .http_client
-            .get("https://shortsmate.com/en/")
-            .send()
-            .await
-            .map_err(|err| anyhow!(err))?;
-        let cookie = get_cookie(res.headers())?;
-        let html = res.text().await.map_err(|err| anyhow!(err))?;
-        let csrf = self.get_csrf(&html)?;
+            .send(HttpRequest::get("https://shortsmate.com/en/"))
+            .await?
+            .error_for_status()?;
+        let cookie = get_cookie(&res.headers)?;
+        let csrf = self.get_csrf(&res.body)?;
         Ok(AuthData { csrf, cookie })
     }
-}
 
-impl<'a> ResolveUrl<'a> for ShortsUrlResolver<'a> {
-    async fn resolve_url(&self, url: &'a str) -> anyhow::Result<Url> {
+    async fn resolve(&self, url: &str) -> anyhow::Result<Vec<ResolvedMedia>> {
         let AuthData { csrf, cookie } = self.get_auth_data().await?;
-        let html = self.http_client.post("https://shortsmate.com/en/download")
+        let html = self.http_client.send(HttpRequest::post("https://shortsmate.com/en/download")
             .form(&[
-                ("csrf_token", csrf),
-                ("url", url.to_string()),
+                ("csrf_token", &csrf),
+                ("url", url),
             ])
             .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/119.0.0.0 Safari/537.36")
             .header("Referer", "https://shortsmate.com/en/download")
-            .header("Cookie", cookie)
-            .send()
+            .header("Cookie", &cookie))
             .await?
-            .text()
-            .await
-            .map_err(|err| anyhow!(err))?;
-        let url = get_media_url(&html)?;
-        Ok(Url::parse(&url)?)
+            .error_for_status()?
+            .body;
+        let formats = sort_formats(&get_formats(&html)?, &self.policy);
+        let (best, rest) = formats
+            .split_first()
+            .ok_or(anyhow!("Cannot get media url"))?;
+
+        let mut video = ResolvedMedia::new(MediaKind::Video, Url::parse(&best.url)?);
+        video.width = best.width;
+        video.height = best.height;
+        video.size = best.size;
+        video.variants = rest
+            .iter()
+            .filter(|it| it.is_playable() && it.height < best.height)
+            .filter_map(|it| {
+                Some(Variant {
+                    url: Url::parse(&it.url).ok()?,
+                    width: it.width,
+                    height: it.height,
+                    size: it.size,
+                })
+            })
+            .collect();
+
+        Ok(vec![video])
+    }
+}
+
+impl<C: HttpClient> ResolveUrl for ShortsUrlResolver<'_, C> {
+    fn resolve_url<'b>(&'b self, url: &'b str) -> LocalBoxFuture<'b, Result<Vec<ResolvedMedia>>> {
+        Box::pin(self.resolve(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    const URL: &str = "https://www.youtube.com/shorts/dQw4w9WgXcQ";
+
+    fn client(index: &str, download: &str) -> FakeHttpClient {
+        FakeHttpClient::new()
+            .fixture("https://shortsmate.com/en/download", download)
+            .fixture("https://shortsmate.com/en/", index)
+            .header(
+                "set-cookie",
+                "session=eyJjc3JmIjoiMSJ9.ZcWx2A; Path=/; HttpOnly",
+            )
+    }
+
+    fn resolve(client: &FakeHttpClient, policy: FormatPolicy) -> Result<Vec<ResolvedMedia>> {
+        block_on(ShortsUrlResolver::new(client, policy).resolve(URL))
+    }
+
+    fn itag(url: &Url) -> String {
+        url.query_pairs()
+            .find(|(name, _)| name == "itag")
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn resolves_best_playable_format() {
+        let client = client("shorts/index.html", "shorts/download.html");
+        let media = resolve(&client, FormatPolicy::HIGH).unwrap();
+
+        assert_eq!(media.len(), 1);
+        let video = &media[0];
+        assert_eq!(itag(&video.url), "22");
+        assert_eq!(video.height, Some(1280));
+        let variants: Vec<_> = video.variants.iter().map(|it| itag(&it.url)).collect();
+        assert_eq!(variants, ["18"]);
+
+        let requests = client.requests.borrow();
+        let download = &requests[1];
+        assert!(download
+            .body
+            .as_deref()
+            .unwrap()
+            .contains("csrf_token=IjA5ZDc3ZjM2YzM0Yjk2NGY0.ZcWx2A"));
+        assert!(download
+            .headers
+            .iter()
+            .any(|(name, value)| name == "Cookie" && value == "session=eyJjc3JmIjoiMSJ9.ZcWx2A;"));
+    }
+
+    #[test]
+    fn resolves_smallest_format_with_low_policy() {
+        let client = client("shorts/index.html", "shorts/download.html");
+        let media = resolve(&client, FormatPolicy::LOW).unwrap();
+
+        assert_eq!(itag(&media[0].url), "18");
+        assert!(media[0].variants.is_empty());
+    }
+
+    #[test]
+    fn fails_without_csrf_token() {
+        let client = client("shorts/index_no_csrf.html", "shorts/download.html");
+        let err = resolve(&client, FormatPolicy::HIGH).unwrap_err();
+
+        assert_eq!(err.to_string(), "Cannot get `csrf_token`");
+        assert_eq!(client.requests.borrow().len(), 1);
+    }
+
+    #[test]
+    fn fails_without_session_cookie() {
+        let client = FakeHttpClient::new()
+            .fixture("https://shortsmate.com/en/download", "shorts/download.html")
+            .fixture("https://shortsmate.com/en/", "shorts/index.html");
+        let err = resolve(&client, FormatPolicy::HIGH).unwrap_err();
+
+        assert_eq!(err.to_string(), "Cannot get `cookie` header");
+        assert_eq!(client.requests.borrow().len(), 1);
+    }
+
+    /// Storyboards are listed as formats but are not media.
+    #[test]
+    fn rejects_storyboards_only_results() {
+        let client = client("shorts/index.html", "shorts/download_empty.html");
+        let err = resolve(&client, FormatPolicy::HIGH).unwrap_err();
+
+        assert_eq!(err.to_string(), "Cannot get media url");
     }
 }
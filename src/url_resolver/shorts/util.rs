@@ -2,7 +2,8 @@ use anyhow::{anyhow, bail, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 use reqwest::header::HeaderMap;
-use serde::Deserialize;
+
+use crate::url_resolver::format::{Format, YtDlpFormat};
 
 lazy_static! {
     pub static ref CSRF_REGEX: Regex =
@@ -12,15 +13,9 @@ lazy_static! {
     pub static ref SESSION_REGEX: Regex = Regex::new(r#"(session=.+?;)"#).unwrap();
 }
 
-#[derive(Debug, Deserialize)]
-struct Media {
-    format_note: String,
-    url: Option<String>,
-}
-
-type MediaList = (Vec<Media>, Vec<Media>);
+type MediaList = (Vec<YtDlpFormat>, Vec<YtDlpFormat>);
 
-pub fn get_media_url(html: &str) -> Result<String> {
+pub fn get_formats(html: &str) -> Result<Vec<Format>> {
     let capts = JSON_REGEX
         .captures(html)
         .ok_or(anyhow!("Cannot capture `json`"))?;
@@ -31,17 +26,15 @@ pub fn get_media_url(html: &str) -> Result<String> {
     json.insert(0, '[');
     json.push(']');
     let list: MediaList = serde_json::from_str(&json)?;
-    let mut list = list.0;
-    list.sort_by(|a, b| {
-        if a.format_note == "1080p" || a.format_note == "720p" {
-            return std::cmp::Ordering::Less;
-        }
-        if a.format_note == "1080p" && b.format_note == "720p" {
-            return std::cmp::Ordering::Less;
-        }
-        std::cmp::Ordering::Equal
-    });
-    list[0].url.clone().ok_or(anyhow!("Cannot get media url"))
+    let formats: Vec<_> = list
+        .0
+        .into_iter()
+        .filter_map(YtDlpFormat::into_format)
+        .collect();
+    if formats.is_empty() {
+        bail!("Cannot get media url");
+    }
+    Ok(formats)
 }
 
 pub fn get_cookie(headers: &HeaderMap) -> Result<String> {
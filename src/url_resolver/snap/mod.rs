@@ -1,35 +1,43 @@
+use std::{fmt::Display, str::FromStr};
+
 use anyhow::{anyhow, bail, Result};
-use reqwest::Client;
+use futures::future::LocalBoxFuture;
+use regex::Captures;
 use url::Url;
 
 mod util;
-use util::{decode, DECODER_ARGS_REGEX, RESULT_VIDEO_URL_REGEX, TOKEN_REGEX};
+pub use util::DecodeError;
+use util::{decode, get_media_kind, DECODER_ARGS_REGEX, RESULT_MEDIA_REGEX, TOKEN_REGEX};
 
-use super::{Platform, ResolveUrl};
+use super::{
+    http::{HttpClient, HttpRequest},
+    MediaKind, Platform, ResolveUrl, ResolvedMedia,
+};
 
 static BOUNDARY: &str = "----WebKitFormBoundary214sQgEtL6ZBo4uE";
 
 #[derive(Debug)]
-pub struct SnapUrlResolver<'a> {
-    client: &'a Client,
+pub struct SnapUrlResolver<'a, C: HttpClient> {
+    client: &'a C,
     platform: &'a Platform,
 }
 
-impl<'a> SnapUrlResolver<'a> {
-    pub fn new(client: &'a Client, platform: &'a Platform) -> Self {
+impl<'a, C: HttpClient> SnapUrlResolver<'a, C> {
+    pub fn new(client: &'a C, platform: &'a Platform) -> Self {
         Self { client, platform }
     }
 
     async fn get_token(&self) -> Result<String> {
         let html = self
             .client
-            .get("https://snaptik.app/en")
-            .send()
+            .send(HttpRequest::get("https://snaptik.app/en"))
             .await?
-            .text()
-            .await?;
+            .error_for_status()?
+            .body;
 
-        let capts = TOKEN_REGEX.captures(&html).unwrap();
+        let capts = TOKEN_REGEX
+            .captures(&html)
+            .ok_or(anyhow!("Unable to get token"))?;
         if capts.len() > 0 {
             return Ok(capts[1].to_string());
         }
@@ -61,27 +69,24 @@ impl<'a> SnapUrlResolver<'a> {
             _ => bail!("Unsupported platform: {:?}", self.platform),
         }
     }
-}
 
-impl<'a> ResolveUrl<'a> for SnapUrlResolver<'a> {
-    async fn resolve_url(&self, url: &'a str) -> Result<Url> {
+    async fn resolve(&self, url: &str) -> Result<Vec<ResolvedMedia>> {
         let multipart_content = self.get_multipart_content(url).await?;
         let endpoint = self.get_endpoint()?;
         let referer = self.get_referer()?;
 
-        let encoded_str = self.client.post(endpoint)
-            .body(multipart_content.to_owned())
+        let encoded_str = self.client.send(HttpRequest::post(endpoint)
             .header("referer", referer)
             .header("user-agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/119.0.0.0 Safari/537.36")
             .header(
                 "content-type",
-                format!("multipart/form-data; boundary={}", BOUNDARY),
+                &format!("multipart/form-data; boundary={}", BOUNDARY),
             )
-            .header("content-length", multipart_content.len().to_string())
-            .send()
+            .header("content-length", &multipart_content.len().to_string())
+            .body(multipart_content))
             .await?
-            .text()
-            .await?;
+            .error_for_status()?
+            .body;
 
         let capts = DECODER_ARGS_REGEX
             .captures(&encoded_str)
@@ -92,20 +97,146 @@ impl<'a> ResolveUrl<'a> for SnapUrlResolver<'a> {
 
         let decoded_str = decode(
             &capts[1],
-            capts[2].parse().unwrap(),
+            parse_arg(&capts, 2)?,
             &capts[3],
-            capts[4].parse().unwrap(),
-            capts[5].parse().unwrap(),
-            capts[6].parse().unwrap(),
-        );
+            parse_arg(&capts, 4)?,
+            parse_arg(&capts, 5)?,
+            parse_arg(&capts, 6)?,
+        )?;
+
+        let mut media: Vec<ResolvedMedia> = vec![];
+        for capts in RESULT_MEDIA_REGEX.captures_iter(&decoded_str) {
+            let url = Url::parse(&capts[1])?;
+            if media.iter().any(|it| it.url == url) {
+                continue;
+            }
+            media.push(ResolvedMedia::new(get_media_kind(&capts[4]), url));
+        }
 
-        let capts = RESULT_VIDEO_URL_REGEX
-            .captures(&decoded_str)
-            .ok_or(anyhow!("Cannot find result URL:\n\n{decoded_str}\n\n"))?;
-        if capts.len() == 0 {
+        if media.is_empty() {
             bail!("Cannot find result URL:\n\n{decoded_str}\n\n");
         }
 
-        Url::parse(&capts[1]).map_err(|err| anyhow!(err))
+        match self.platform {
+            // TikTok offers several variants of the same video, but every
+            // picture of a photo slideshow is a separate item.
+            Platform::TikTok => {
+                if media.iter().any(|it| it.kind == MediaKind::Photo) {
+                    media.retain(|it| it.kind == MediaKind::Photo);
+                } else {
+                    media.truncate(1);
+                }
+                Ok(media)
+            }
+            _ => Ok(media),
+        }
+    }
+}
+
+fn parse_arg<T>(capts: &Captures, idx: usize) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    capts[idx]
+        .parse()
+        .map_err(|err| anyhow!("Invalid decoder argument `{}`: {err}", &capts[idx]))
+}
+
+impl<C: HttpClient> ResolveUrl for SnapUrlResolver<'_, C> {
+    fn resolve_url<'b>(&'b self, url: &'b str) -> LocalBoxFuture<'b, Result<Vec<ResolvedMedia>>> {
+        Box::pin(self.resolve(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    const TIKTOK_URL: &str = "https://www.tiktok.com/@catlover/video/7300000000000000001";
+    const INSTAGRAM_URL: &str = "https://www.instagram.com/p/CxYz123AbC/";
+
+    fn resolve(
+        client: &FakeHttpClient,
+        platform: &Platform,
+        url: &str,
+    ) -> Result<Vec<ResolvedMedia>> {
+        block_on(SnapUrlResolver::new(client, platform).resolve(url))
+    }
+
+    /// Snaptik lists HD, SD and MP3 links of a video, only the first is kept.
+    #[test]
+    fn resolves_tiktok_video_in_best_quality() {
+        let client = FakeHttpClient::new()
+            .fixture("https://snaptik.app/en", "snap/token.html")
+            .fixture("https://snaptik.app/abc2.php", "snap/snaptik_result.js");
+        let media = resolve(&client, &Platform::TikTok, TIKTOK_URL).unwrap();
+
+        assert_eq!(media.len(), 1);
+        assert_eq!(media[0].kind, MediaKind::Video);
+        assert_eq!(
+            media[0].url.as_str(),
+            "https://d.rapidcdn.app/v2?token=video-hd"
+        );
+
+        let requests = client.requests.borrow();
+        assert_eq!(requests[1].url, "https://snaptik.app/abc2.php");
+        let body = requests[1].body.as_deref().unwrap();
+        assert!(body.contains("eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9"));
+        assert!(body.contains(TIKTOK_URL));
+    }
+
+    /// Every picture of a slideshow is a separate item, the soundtrack is dropped.
+    #[test]
+    fn resolves_tiktok_slideshow_photos_in_order() {
+        let client = FakeHttpClient::new()
+            .fixture("https://snaptik.app/en", "snap/token.html")
+            .fixture("https://snaptik.app/abc2.php", "snap/slideshow_result.js");
+        let media = resolve(&client, &Platform::TikTok, TIKTOK_URL).unwrap();
+
+        let urls: Vec<_> = media.iter().map(|it| it.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://d.rapidcdn.app/v2?token=photo-1",
+                "https://d.rapidcdn.app/v2?token=photo-2",
+                "https://d.rapidcdn.app/v2?token=photo-3",
+            ]
+        );
+        assert!(media.iter().all(|it| it.kind == MediaKind::Photo));
+    }
+
+    #[test]
+    fn resolves_instagram_post_without_duplicates() {
+        let client = FakeHttpClient::new()
+            .fixture("https://snaptik.app/en", "snap/token.html")
+            .fixture(
+                "https://snapinsta.app/action2.php",
+                "snap/snapinsta_result.js",
+            );
+        let media = resolve(&client, &Platform::Instagram, INSTAGRAM_URL).unwrap();
+
+        let kinds: Vec<_> = media.iter().map(|it| it.kind).collect();
+        assert_eq!(kinds, [MediaKind::Photo, MediaKind::Video]);
+
+        let requests = client.requests.borrow();
+        assert_eq!(requests[1].url, "https://snapinsta.app/action2.php");
+        assert!(requests[1]
+            .headers
+            .contains(&("referer".to_string(), "https://snapinsta.app/".to_string())));
+    }
+
+    #[test]
+    fn fails_without_token() {
+        let client = FakeHttpClient::new()
+            .fixture("https://snaptik.app/en", "snap/token_missing.html")
+            .fixture("https://snaptik.app/abc2.php", "snap/snaptik_result.js");
+        let err = resolve(&client, &Platform::TikTok, TIKTOK_URL).unwrap_err();
+
+        assert_eq!(err.to_string(), "Unable to get token");
+        assert_eq!(client.requests.borrow().len(), 1);
     }
 }
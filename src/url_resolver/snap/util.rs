@@ -1,6 +1,10 @@
+use std::num::IntErrorKind;
+
 use lazy_static::lazy_static;
 use regex::Regex;
 
+use crate::url_resolver::MediaKind;
+
 lazy_static! {
     pub static ref TOKEN_REGEX: Regex =
         Regex::new(r#"<input name="token" value="(.+?)" .+?>"#).unwrap();
@@ -12,32 +16,90 @@ lazy_static! {
 }
 
 lazy_static! {
-    pub static ref RESULT_VIDEO_URL_REGEX: Regex = Regex::new(
-        r#"href=\\?"(https://(.*?\.)?(snaptik\.app|snapinsta\.app|rapidcdn\.app)/.*?)\\?""#
+    pub static ref RESULT_MEDIA_REGEX: Regex = Regex::new(
+        r#"(?s)href=\\?"(https://(.*?\.)?(snaptik\.app|snapinsta\.app|rapidcdn\.app)/.*?)\\?".*?>(.*?)<\\?/a>"#
     )
     .unwrap();
 }
 
-pub fn decode(h: &str, _u: usize, n: &str, t: u32, e: usize, _r: usize) -> String {
-    let mut result = String::from("");
+pub fn get_media_kind(link_text: &str) -> MediaKind {
+    if link_text.contains("Photo") || link_text.contains("Image") {
+        MediaKind::Photo
+    } else if link_text.contains("MP3") || link_text.contains("Audio") {
+        MediaKind::Audio
+    } else {
+        MediaKind::Video
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Base is not within `2..=36` or does not point to a delimiter in the alphabet.
+    BadBase(usize),
+    /// Segment starting at the given position is not terminated by a delimiter.
+    MissingDelimiter(usize),
+    /// Segment cannot be parsed as a number in the given base.
+    InvalidDigits(String),
+    /// Decoded number is not a byte of the UTF-8 encoded result.
+    InvalidCharCode(u32),
+    /// Segment with the given digits is too large for a char code.
+    Overflow(String),
+    /// Decoded number is less than the offset subtracted from it.
+    Underflow { value: u32, offset: u32 },
+    /// Decoded bytes are not valid UTF-8.
+    InvalidUtf8,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadBase(base) => write!(f, "Bad base: {base}"),
+            Self::MissingDelimiter(position) => {
+                write!(f, "Missing delimiter after position {position}")
+            }
+            Self::InvalidDigits(digits) => write!(f, "Invalid digits: {digits}"),
+            Self::InvalidCharCode(code) => write!(f, "Invalid char code: {code}"),
+            Self::Overflow(digits) => write!(f, "Overflow on {digits}"),
+            Self::Underflow { value, offset } => {
+                write!(f, "Underflow on {value} - {offset}")
+            }
+            Self::InvalidUtf8 => write!(f, "Invalid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Port of the `eval`ed decoder of snaptik and snapinsta pages. Every
+/// delimited segment of `h` is a byte of the UTF-8 result written in base `e`
+/// with digits of `n` and shifted by `t`.
+pub fn decode(
+    h: &str,
+    _u: usize,
+    n: &str,
+    t: u32,
+    e: usize,
+    _r: usize,
+) -> Result<String, DecodeError> {
+    let mut bytes = vec![];
     let h_chars: Vec<char> = h.chars().collect();
     let n_chars: Vec<char> = n.chars().collect();
 
+    let radix = match u32::try_from(e) {
+        Ok(radix) if (2..=36).contains(&radix) => radix,
+        _ => return Err(DecodeError::BadBase(e)),
+    };
+    let delimiter = n_chars.get(e).ok_or(DecodeError::BadBase(e))?;
+
     let mut i = 0;
-    while i < h.len() {
+    while i < h_chars.len() {
+        let start = i;
         let mut s = String::from("");
 
         loop {
-            let h_ch = match h_chars.get(i) {
-                Some(ch) => ch,
-                None => panic!("Cannot get char by index: {}", i),
-            };
-            let n_ch = match n_chars.get(e) {
-                Some(ch) => ch,
-                None => panic!("Cannot get char by index: {}", e),
-            };
-
-            if h_ch == n_ch {
+            let h_ch = h_chars.get(i).ok_or(DecodeError::MissingDelimiter(start))?;
+
+            if h_ch == delimiter {
                 break;
             }
 
@@ -45,37 +107,211 @@ pub fn decode(h: &str, _u: usize, n: &str, t: u32, e: usize, _r: usize) -> Strin
             i += 1;
         }
 
-        let mut j = 0;
-        while j < n.len() {
-            let ch = match n_chars.get(j) {
-                Some(ch) => ch,
-                None => panic!("Cannot get char by index: {}", j),
-            };
+        for (j, ch) in n_chars.iter().enumerate() {
             s = s.replace(*ch, &j.to_string());
-            j += 1;
         }
 
         i += 1;
 
-        let e_u32: u32 = match e.try_into() {
-            Ok(e) => e,
-            Err(err) => panic!("{}", err),
-        };
-        let char_code = match usize::from_str_radix(&s, e_u32) {
-            Ok(result) => match u32::try_from(result) {
-                Ok(result) => result,
-                Err(err) => panic!("{}", err),
-            },
-            Err(err) => panic!("{}", err),
-        } - t;
-
-        let char = match char::from_u32(char_code) {
-            Some(result) => result,
-            None => panic!("Cannot create char by code: {}", char_code),
-        };
-
-        result.push(char);
+        let value = u32::from_str_radix(&s, radix).map_err(|err| match err.kind() {
+            IntErrorKind::PosOverflow => DecodeError::Overflow(s.clone()),
+            _ => DecodeError::InvalidDigits(s.clone()),
+        })?;
+        let char_code = value
+            .checked_sub(t)
+            .ok_or(DecodeError::Underflow { value, offset: t })?;
+        let byte = u8::try_from(char_code).map_err(|_| DecodeError::InvalidCharCode(char_code))?;
+
+        bytes.push(byte);
+    }
+
+    String::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Inverse of [`decode`]: writes every byte of `text` shifted by `t` in
+    /// base `e` with digits of `n`, followed by the delimiter `n[e]`.
+    fn encode(text: &str, n: &str, t: u32, e: usize) -> String {
+        let digits: Vec<char> = n.chars().collect();
+        let mut encoded = String::new();
+        for byte in text.bytes() {
+            let mut value = byte as u32 + t;
+            let mut segment = vec![];
+            loop {
+                segment.push(digits[(value % e as u32) as usize]);
+                value /= e as u32;
+                if value == 0 {
+                    break;
+                }
+            }
+            encoded.extend(segment.iter().rev());
+            encoded.push(digits[e]);
+        }
+        encoded
+    }
+
+    /// Alphabets of `e + 1` distinct letters, the last one is the delimiter.
+    /// Digits are replaced by their decimal indexes, so like the real pages
+    /// bases stay below 10.
+    fn alphabet() -> impl Strategy<Value = (String, usize)> {
+        (2usize..10).prop_flat_map(|e| {
+            Just(('a'..='z').chain('A'..='Z').collect::<Vec<_>>())
+                .prop_shuffle()
+                .prop_map(move |letters| (letters[..=e].iter().collect(), e))
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn decodes_encoded_text(text in "\\PC*", (n, e) in alphabet(), t in 0u32..100) {
+            let encoded = encode(&text, &n, t, e);
+            prop_assert_eq!(decode(&encoded, 0, &n, t, e, 0), Ok(text));
+        }
+
+        #[test]
+        fn does_not_panic_on_arbitrary_input(
+            h in "\\PC*",
+            n in "\\PC*",
+            t in any::<u32>(),
+            e in any::<usize>(),
+        ) {
+            let _ = decode(&h, 0, &n, t, e, 0);
+        }
+
+        #[test]
+        fn does_not_panic_on_arbitrary_segments(
+            h in "[a-k]*",
+            (n, e) in alphabet(),
+            t in any::<u32>(),
+        ) {
+            let _ = decode(&h, 0, &n, t, e, 0);
+        }
+    }
+
+    fn read_fixture(file: &str) -> String {
+        let path = format!("{}/tests/fixtures/snap/{file}", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(path).unwrap()
     }
 
-    result
+    fn decode_fixture(file: &str) -> Result<String, DecodeError> {
+        let js = read_fixture(file);
+        let capts = DECODER_ARGS_REGEX.captures(&js).unwrap();
+        decode(
+            &capts[1],
+            capts[2].parse().unwrap(),
+            &capts[3],
+            capts[4].parse().unwrap(),
+            capts[5].parse().unwrap(),
+            capts[6].parse().unwrap(),
+        )
+    }
+
+    /// Payloads are made in the format of the pages, expected results are
+    /// produced by the original JavaScript decoder.
+    #[test]
+    fn decodes_like_reference() {
+        for name in [
+            "snaptik_result",
+            "snapinsta_result",
+            "empty_result",
+            "unicode_result",
+        ] {
+            assert_eq!(
+                decode_fixture(&format!("{name}.js")).unwrap(),
+                read_fixture(&format!("{name}.txt")),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_media_in_decoded_result() {
+        let decoded = decode_fixture("snaptik_result.js").unwrap();
+        let media: Vec<_> = RESULT_MEDIA_REGEX
+            .captures_iter(&decoded)
+            .map(|capts| (capts[1].to_string(), get_media_kind(&capts[4])))
+            .collect();
+        assert_eq!(
+            media,
+            [
+                (
+                    "https://d.rapidcdn.app/v2?token=video-hd".to_string(),
+                    MediaKind::Video
+                ),
+                (
+                    "https://d.rapidcdn.app/v2?token=video-sd".to_string(),
+                    MediaKind::Video
+                ),
+                (
+                    "https://d.rapidcdn.app/v2?token=audio".to_string(),
+                    MediaKind::Audio
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_base() {
+        assert_eq!(decode("", 0, "abc", 0, 1, 0), Err(DecodeError::BadBase(1)));
+        assert_eq!(
+            decode("", 0, "0123456789abcdefghijklmnopqrstuvwxyzAB", 0, 37, 0),
+            Err(DecodeError::BadBase(37))
+        );
+        // The delimiter is the `e`-th char of the alphabet.
+        assert_eq!(decode("", 0, "abc", 0, 3, 0), Err(DecodeError::BadBase(3)));
+    }
+
+    #[test]
+    fn rejects_bad_digits() {
+        // `q` is not in the alphabet.
+        assert_eq!(
+            decode("abqc", 0, "abc", 0, 2, 0),
+            Err(DecodeError::InvalidDigits("01q".to_string()))
+        );
+        // `d` stands for `3`, which is not a digit in base 2.
+        assert_eq!(
+            decode("adc", 0, "abcd", 0, 2, 0),
+            Err(DecodeError::InvalidDigits("03".to_string()))
+        );
+        assert_eq!(
+            decode("ab", 0, "abc", 0, 2, 0),
+            Err(DecodeError::MissingDelimiter(0))
+        );
+        assert_eq!(
+            decode("c", 0, "abc", 0, 2, 0),
+            Err(DecodeError::InvalidDigits("".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_char_codes() {
+        // 256 in base 16 does not fit into a byte.
+        assert_eq!(
+            decode("baaz", 0, "abcdefghijklmnopz", 0, 16, 0),
+            Err(DecodeError::InvalidCharCode(256))
+        );
+        // 65 - 66
+        assert_eq!(
+            decode("ebz", 0, "abcdefghijklmnopz", 66, 16, 0),
+            Err(DecodeError::Underflow {
+                value: 65,
+                offset: 66
+            })
+        );
+        // 16^9 does not fit into u32 on any target.
+        assert_eq!(
+            decode("baaaaaaaaaz", 0, "abcdefghijklmnopz", 0, 16, 0),
+            Err(DecodeError::Overflow("1000000000".to_string()))
+        );
+        // A lone continuation byte `0x80`.
+        assert_eq!(
+            decode("iaz", 0, "abcdefghijklmnopz", 0, 16, 0),
+            Err(DecodeError::InvalidUtf8)
+        );
+    }
 }
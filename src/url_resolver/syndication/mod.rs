@@ -0,0 +1,261 @@
+use std::cmp::Reverse;
+
+use anyhow::{bail, Result};
+use futures::future::LocalBoxFuture;
+use url::Url;
+
+use crate::telegram::UPLOAD_LIMIT;
+
+use super::{
+    http::{HttpClient, HttpRequest},
+    MediaKind, ResolveUrl, ResolvedMedia, Variant,
+};
+
+mod util;
+use util::{get_token, get_tweet_id, MediaDetails, Tweet, VideoVariant, VARIANT_SIZE_REGEX};
+
+/// Resolves tweets using JSON of the syndication endpoint behind embedded tweets.
+#[derive(Debug)]
+pub struct SyndicationUrlResolver<'a, C: HttpClient> {
+    http_client: &'a C,
+}
+
+impl<'a, C: HttpClient> SyndicationUrlResolver<'a, C> {
+    pub fn new(http_client: &'a C) -> Self {
+        Self { http_client }
+    }
+
+    async fn get_tweet(&self, id: &str) -> Result<Tweet> {
+        let url = Url::parse_with_params(
+            "https://cdn.syndication.twimg.com/tweet-result",
+            &[("id", id), ("token", &get_token(id)?), ("lang", "en")],
+        )?;
+        let json = self
+            .http_client
+            .send(HttpRequest::get(url.as_str()).header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:122.0) Gecko/20100101 Firefox/122.0"))
+            .await?
+            .error_for_status()?
+            .body;
+        let tweet: Tweet = serde_json::from_str(&json)?;
+        if tweet.typename.as_deref() == Some("TweetTombstone") {
+            bail!("Tweet {id} is unavailable");
+        }
+        Ok(tweet)
+    }
+
+    async fn resolve(&self, url: &str) -> Result<Vec<ResolvedMedia>> {
+        let tweet = self.get_tweet(&get_tweet_id(url)?).await?;
+
+        // Media of a quoted tweet is shown when the tweet itself has none.
+        let has_media = tweet
+            .media_details
+            .as_ref()
+            .is_some_and(|it| !it.is_empty());
+        let tweet = match tweet.quoted_tweet {
+            Some(quoted_tweet) if !has_media => *quoted_tweet,
+            _ => tweet,
+        };
+        let author = tweet.user.map(|user| user.screen_name);
+        // Text of tweets with media ends with a `t.co` link to it.
+        let title = tweet
+            .text
+            .as_deref()
+            .map(|text| match text.rsplit_once("https://t.co/") {
+                Some((text, _)) => text.trim(),
+                None => text.trim(),
+            })
+            .filter(|it| !it.is_empty())
+            .map(|it| it.to_string());
+
+        let mut media = vec![];
+        for details in tweet.media_details.unwrap_or_default() {
+            if let Some(mut it) = get_media(&details)? {
+                it.author = author.clone();
+                it.title = title.clone();
+                media.push(it);
+            }
+        }
+
+        if media.is_empty() {
+            bail!("Tweet has no media");
+        }
+        Ok(media)
+    }
+}
+
+fn get_media(details: &MediaDetails) -> Result<Option<ResolvedMedia>> {
+    let (width, height) = details
+        .original_info
+        .as_ref()
+        .map_or((None, None), |it| (it.width, it.height));
+
+    let video_info = match (details.media_type.as_str(), &details.video_info) {
+        ("photo", _) => {
+            let mut photo =
+                ResolvedMedia::new(MediaKind::Photo, Url::parse(&details.media_url_https)?);
+            photo.width = width;
+            photo.height = height;
+            return Ok(Some(photo));
+        }
+        ("video" | "animated_gif", Some(video_info)) => video_info,
+        _ => return Ok(None),
+    };
+
+    let mut variants: Vec<_> = video_info
+        .variants
+        .iter()
+        .filter(|it| it.content_type == "video/mp4")
+        .collect();
+    if variants.is_empty() {
+        return Ok(None);
+    }
+    variants.sort_by_key(|it| Reverse(it.bitrate.unwrap_or_default()));
+
+    let duration_millis = video_info.duration_millis;
+    let idx = variants
+        .iter()
+        .position(|it| match (it.bitrate, duration_millis) {
+            (Some(bitrate), Some(duration_millis)) => {
+                // Larger videos are replaced with lower bitrate variants.
+                bitrate * duration_millis / 1000 / 8 <= UPLOAD_LIMIT as u64
+            }
+            _ => true,
+        })
+        .unwrap_or(variants.len() - 1);
+
+    let best = get_variant(variants[idx])?;
+    let mut video = ResolvedMedia::new(MediaKind::Video, best.url);
+    // GIFs have no resolution in their URLs.
+    video.width = best.width.or(width);
+    video.height = best.height.or(height);
+    video.thumbnail = Url::parse(&details.media_url_https).ok();
+    video.duration = duration_millis.map(|it| (it / 1000) as u32);
+    video.variants = variants[idx + 1..]
+        .iter()
+        .map(|it| get_variant(it))
+        .collect::<Result<_>>()?;
+
+    Ok(Some(video))
+}
+
+fn get_variant(variant: &VideoVariant) -> Result<Variant> {
+    let size = VARIANT_SIZE_REGEX
+        .captures(&variant.url)
+        .and_then(|capts| Some((capts[1].parse().ok()?, capts[2].parse().ok()?)));
+    Ok(Variant {
+        url: Url::parse(&variant.url)?,
+        width: size.map(|(width, _)| width),
+        height: size.map(|(_, height)| height),
+        size: None,
+    })
+}
+
+impl<C: HttpClient> ResolveUrl for SyndicationUrlResolver<'_, C> {
+    fn resolve_url<'b>(&'b self, url: &'b str) -> LocalBoxFuture<'b, Result<Vec<ResolvedMedia>>> {
+        Box::pin(self.resolve(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    const ENDPOINT: &str = "https://cdn.syndication.twimg.com/tweet-result";
+
+    fn resolve(fixture: &str) -> Result<Vec<ResolvedMedia>> {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, fixture);
+        block_on(
+            SyndicationUrlResolver::new(&client)
+                .resolve("https://x.com/example/status/1726500000000000000"),
+        )
+    }
+
+    #[test]
+    fn resolves_video_variants() {
+        let media = resolve("syndication/video.json").unwrap();
+
+        assert_eq!(media.len(), 1);
+        let video = &media[0];
+        assert_eq!(video.kind, MediaKind::Video);
+        assert!(video.url.path().ends_with("/1280x720/high.mp4"));
+        assert_eq!((video.width, video.height), (Some(1280), Some(720)));
+        assert_eq!(video.duration, Some(30));
+        assert_eq!(video.author.as_deref(), Some("example"));
+        assert_eq!(video.title.as_deref(), Some("Look at this"));
+        assert!(video.thumbnail.is_some());
+        let variants: Vec<_> = video.variants.iter().map(|it| it.height).collect();
+        assert_eq!(variants, [Some(360), Some(270)]);
+    }
+
+    #[test]
+    fn resolves_photos_in_order() {
+        let media = resolve("syndication/photos.json").unwrap();
+
+        let photos: Vec<_> = media
+            .iter()
+            .map(|it| (it.kind, it.url.path(), it.width, it.height))
+            .collect();
+        assert_eq!(
+            photos,
+            [
+                (MediaKind::Photo, "/media/first.jpg", Some(2048), Some(1536)),
+                (
+                    MediaKind::Photo,
+                    "/media/second.jpg",
+                    Some(1200),
+                    Some(1600)
+                ),
+            ]
+        );
+        assert_eq!(media[1].title.as_deref(), Some("Two pictures"));
+    }
+
+    #[test]
+    fn skips_variants_estimated_over_upload_limit() {
+        let media = resolve("syndication/long_video.json").unwrap();
+
+        // 10 minutes at 2176 and 832 kbps exceed the limit, 256 kbps fits.
+        assert!(media[0].url.path().ends_with("/480x270/low.mp4"));
+        assert_eq!(media[0].height, Some(270));
+        assert!(media[0].variants.is_empty());
+        assert_eq!(media[0].title.as_deref(), Some("Full talk"));
+    }
+
+    #[test]
+    fn resolves_gif_size_from_original_info() {
+        let media = resolve("syndication/gif.json").unwrap();
+
+        assert_eq!(media[0].kind, MediaKind::Video);
+        assert_eq!((media[0].width, media[0].height), (Some(498), Some(280)));
+        // Text consists of the media link only.
+        assert_eq!(media[0].title, None);
+    }
+
+    #[test]
+    fn resolves_quoted_tweet_media() {
+        let media = resolve("syndication/quoted.json").unwrap();
+
+        assert_eq!(media.len(), 1);
+        assert_eq!(
+            media[0].url.as_str(),
+            "https://pbs.twimg.com/media/quoted.jpg"
+        );
+        assert_eq!(media[0].author.as_deref(), Some("original"));
+        assert_eq!(media[0].title.as_deref(), Some("Original picture"));
+    }
+
+    #[test]
+    fn rejects_tweets_without_media() {
+        let err = resolve("syndication/text.json").unwrap_err();
+        assert_eq!(err.to_string(), "Tweet has no media");
+    }
+
+    #[test]
+    fn rejects_unavailable_tweets() {
+        let err = resolve("syndication/tombstone.json").unwrap_err();
+        assert_eq!(err.to_string(), "Tweet 1726500000000000000 is unavailable");
+    }
+}
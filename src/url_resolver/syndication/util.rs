@@ -0,0 +1,218 @@
+use std::f64::consts::PI;
+
+use anyhow::{anyhow, bail, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use url::Url;
+
+lazy_static! {
+    /// Resolution in paths of video URLs, e.g. `/vid/avc1/1280x720/`.
+    pub static ref VARIANT_SIZE_REGEX: Regex = Regex::new(r"/(\d+)x(\d+)/").unwrap();
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Tweet {
+    #[serde(rename = "__typename")]
+    pub typename: Option<String>,
+    pub text: Option<String>,
+    pub user: Option<User>,
+    #[serde(rename = "mediaDetails")]
+    pub media_details: Option<Vec<MediaDetails>>,
+    pub quoted_tweet: Option<Box<Tweet>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub screen_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaDetails {
+    /// `photo`, `video` or `animated_gif`.
+    #[serde(rename = "type")]
+    pub media_type: String,
+    pub media_url_https: String,
+    pub original_info: Option<OriginalInfo>,
+    pub video_info: Option<VideoInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OriginalInfo {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VideoInfo {
+    pub duration_millis: Option<u64>,
+    pub variants: Vec<VideoVariant>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VideoVariant {
+    pub bitrate: Option<u64>,
+    pub content_type: String,
+    pub url: String,
+}
+
+/// Extracts the tweet ID from `/<user>/status/<id>` links.
+pub fn get_tweet_id(url: &str) -> Result<String> {
+    let url = Url::parse(url)?;
+    let mut segments = url
+        .path_segments()
+        .ok_or(anyhow!("Cannot get tweet ID: {url}"))?;
+    segments
+        .find(|it| *it == "status")
+        .and_then(|_| segments.next())
+        .filter(|id| !id.is_empty() && id.chars().all(|ch| ch.is_ascii_digit()))
+        .map(|id| id.to_string())
+        .ok_or(anyhow!("Cannot get tweet ID: {url}"))
+}
+
+/// Token the embed widget sends along with the ID:
+/// `((id / 1e15) * Math.PI).toString(36).replace(/(0+|\.)/g, '')`.
+pub fn get_token(id: &str) -> Result<String> {
+    let id: f64 = id.parse()?;
+    let token: String = to_radix_string(id / 1e15 * PI, 36)
+        .chars()
+        .filter(|it| *it != '0' && *it != '.')
+        .collect();
+    if token.is_empty() {
+        bail!("Cannot get token for {id}");
+    }
+    Ok(token)
+}
+
+/// Port of `DoubleToRadixCString` of V8 behind `Number.prototype.toString`
+/// for positive `value`: fraction digits stop as soon as they identify the
+/// double, the last one rounded half to even.
+fn to_radix_string(value: f64, radix: u32) -> String {
+    let next_double = |value: f64| f64::from_bits(value.to_bits() + 1);
+
+    let mut integer = value.floor();
+    let mut fraction = value - integer;
+    let mut delta = (0.5 * (next_double(value) - value)).max(next_double(0.0));
+
+    let mut fraction_digits = vec![];
+    if fraction >= delta {
+        loop {
+            fraction *= radix as f64;
+            delta *= radix as f64;
+            let digit = fraction as u32;
+            fraction_digits.push(digit);
+            fraction -= digit as f64;
+            if (fraction > 0.5 || (fraction == 0.5 && digit & 1 == 1)) && fraction + delta > 1.0 {
+                // Round up, carrying over into preceding digits.
+                loop {
+                    match fraction_digits.pop() {
+                        Some(digit) if digit + 1 < radix => {
+                            fraction_digits.push(digit + 1);
+                            break;
+                        }
+                        Some(_) => continue,
+                        None => {
+                            integer += 1.0;
+                            break;
+                        }
+                    }
+                }
+                break;
+            }
+            if fraction < delta {
+                break;
+            }
+        }
+    }
+
+    // Digits beyond the precision of the double are zeros.
+    let mut integer_digits = vec![];
+    while exponent(integer / radix as f64) > 0 {
+        integer /= radix as f64;
+        integer_digits.push(0);
+    }
+    loop {
+        let remainder = integer % radix as f64;
+        integer_digits.push(remainder as u32);
+        integer = (integer - remainder) / radix as f64;
+        if integer <= 0.0 {
+            break;
+        }
+    }
+    integer_digits.reverse();
+
+    let to_char = |digit: &u32| char::from_digit(*digit, radix).unwrap_or('0');
+    let mut result: String = integer_digits.iter().map(to_char).collect();
+    if !fraction_digits.is_empty() {
+        result.push('.');
+        result.extend(fraction_digits.iter().map(to_char));
+    }
+    result
+}
+
+/// Binary exponent of `value` with the significand taken as a 53-bit integer.
+fn exponent(value: f64) -> i64 {
+    let biased = ((value.to_bits() >> 52) & 0x7FF) as i64;
+    if biased == 0 {
+        -1074
+    } else {
+        biased - 1075
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tokens computed by the JavaScript expression of the embed widget.
+    #[test]
+    fn gets_token_like_widget() {
+        let cases = [
+            ("20", "6dq1a2xwd93"),
+            ("463440424141459456", "14fxvks611f"),
+            ("1234567890123456789", "2zqic77uqyk"),
+            ("1700000000000000000", "44cpgxmyurn"),
+            ("1726500000000000000", "46nyjsj24o7"),
+            ("1790000000000000001", "4c7g8auqyik"),
+        ];
+        for (id, token) in cases {
+            assert_eq!(get_token(id).unwrap(), token, "{id}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_ids() {
+        assert!(get_token("").is_err());
+        assert!(get_token("abc").is_err());
+        assert!(get_token("0").is_err());
+    }
+
+    /// Results of `Number.prototype.toString(radix)`.
+    #[test]
+    fn prints_numbers_like_javascript() {
+        let cases = [
+            (0.5, 36, "0.i"),
+            (255.5, 16, "ff.8"),
+            (PI, 36, "3.53i5ab8p5f"),
+            (PI, 2, "11.001001000011111101101010100010001000010110100011"),
+            (0.1, 3, "0.0022002200220022002200220022002201"),
+            (123.456, 36, "3f.gez4w97ry"),
+            (1.0 - f64::EPSILON / 2.0, 36, "0.zzzzzzzzzza"),
+            (1e21, 36, "5v1j4f4ds7c000"),
+            (9007199254740994.0, 36, "2gosa7pa2gy"),
+        ];
+        for (value, radix, expected) in cases {
+            assert_eq!(to_radix_string(value, radix), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn gets_tweet_id() {
+        assert_eq!(
+            get_tweet_id("https://x.com/user/status/1700000000000000000/photo/1").unwrap(),
+            "1700000000000000000"
+        );
+        assert!(get_tweet_id("https://x.com/user/status/abc").is_err());
+        assert!(get_tweet_id("https://x.com/user").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{anyhow, bail, Result};
+use futures::future::LocalBoxFuture;
+use url::Url;
+
+use super::{
+    http::{HttpClient, HttpRequest},
+    MediaKind, ResolveUrl, ResolvedMedia, Variant,
+};
+
+mod util;
+use util::{get_cdn_cookie, get_item_struct};
+
+/// Resolves TikTok links using the page data TikTok embeds into its own HTML.
+#[derive(Debug)]
+pub struct TikTokUrlResolver<'a, C: HttpClient> {
+    http_client: &'a C,
+}
+
+impl<'a, C: HttpClient> TikTokUrlResolver<'a, C> {
+    pub fn new(http_client: &'a C) -> Self {
+        Self { http_client }
+    }
+
+    async fn resolve(&self, url: &str) -> Result<Vec<ResolvedMedia>> {
+        let response = self.http_client.send(HttpRequest::get(url)
+            .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/119.0.0.0 Safari/537.36"))
+            .await?
+            .error_for_status()?;
+        let item = get_item_struct(&response.body)?;
+        let author = item.author.map(|author| author.unique_id);
+        let title = item.desc.filter(|it| !it.is_empty());
+
+        if let Some(image_post) = item.image_post {
+            let mut media = vec![];
+            for image in image_post.images {
+                let url = image
+                    .image_url
+                    .url_list
+                    .first()
+                    .ok_or(anyhow!("Cannot get image URL"))?;
+                let mut photo = ResolvedMedia::new(MediaKind::Photo, Url::parse(url)?);
+                photo.author = author.clone();
+                photo.title = title.clone();
+                media.push(photo);
+            }
+            if media.is_empty() {
+                bail!("Photo post has no images");
+            }
+            return Ok(media);
+        }
+
+        let play_addr = item
+            .video
+            .play_addr
+            .filter(|it| !it.is_empty())
+            .ok_or(anyhow!("Cannot get `playAddr`"))?;
+        let mut video = ResolvedMedia::new(MediaKind::Video, Url::parse(&play_addr)?);
+        video.width = item.video.width;
+        video.height = item.video.height;
+        video.duration = item.video.duration;
+        video.author = author;
+        video.title = title;
+        video.thumbnail = item.video.cover.and_then(|it| Url::parse(&it).ok());
+        // CDN rejects requests without a TikTok referer and cookies of the page.
+        video.headers = vec![("Referer".to_string(), "https://www.tiktok.com/".to_string())];
+        if let Some(cookie) = get_cdn_cookie(&response.headers) {
+            video.headers.push(("Cookie".to_string(), cookie));
+        }
+
+        let mut bitrate_info = item.video.bitrate_info.unwrap_or_default();
+        bitrate_info.sort_by_key(|it| std::cmp::Reverse(it.bitrate));
+        video.variants = bitrate_info
+            .iter()
+            .filter(|it| match (it.play_addr.height, video.height) {
+                (Some(height), Some(max_height)) => height < max_height,
+                _ => true,
+            })
+            .filter_map(|it| {
+                Some(Variant {
+                    url: Url::parse(it.play_addr.url_list.first()?).ok()?,
+                    width: it.play_addr.width,
+                    height: it.play_addr.height,
+                    size: it.play_addr.size(),
+                })
+            })
+            .collect();
+
+        Ok(vec![video])
+    }
+}
+
+impl<C: HttpClient> ResolveUrl for TikTokUrlResolver<'_, C> {
+    fn resolve_url<'b>(&'b self, url: &'b str) -> LocalBoxFuture<'b, Result<Vec<ResolvedMedia>>> {
+        Box::pin(self.resolve(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    const URL: &str = "https://www.tiktok.com/@catlover/video/7300000000000000001";
+
+    fn resolve(client: &FakeHttpClient) -> Result<Vec<ResolvedMedia>> {
+        block_on(TikTokUrlResolver::new(client).resolve(URL))
+    }
+
+    #[test]
+    fn resolves_video() {
+        let client = FakeHttpClient::new()
+            .fixture(URL, "tiktok/video.html")
+            .header(
+                "set-cookie",
+                "tt_chain_token=AbCdEf123==; Max-Age=15552000; Domain=.tiktok.com; Path=/; Secure; HttpOnly",
+            )
+            .header("set-cookie", "tt_csrf_token=xyz; Path=/; Secure")
+            .header(
+                "set-cookie",
+                "ttwid=1%7CAbC%7C1700000000%7Cdef; Domain=.tiktok.com; Path=/; Expires=Wed, 06 Nov 2024 08:00:00 GMT; HttpOnly",
+            );
+        let media = resolve(&client).unwrap();
+
+        assert_eq!(media.len(), 1);
+        let video = &media[0];
+        assert_eq!(video.kind, MediaKind::Video);
+        assert_eq!(
+            video.url.as_str(),
+            "https://v16-webapp-prime.tiktok.com/video/tos/1080.mp4?x-expires=1700000000&x-signature=abc"
+        );
+        assert_eq!(video.expires_at, Some(1700000000));
+        assert_eq!((video.width, video.height), (Some(1080), Some(1920)));
+        assert_eq!(video.duration, Some(15));
+        assert_eq!(video.author.as_deref(), Some("catlover"));
+        assert_eq!(video.title.as_deref(), Some("Cats #fyp"));
+        assert!(video.thumbnail.is_some());
+        assert_eq!(
+            video.headers,
+            [
+                ("Referer".to_string(), "https://www.tiktok.com/".to_string()),
+                (
+                    "Cookie".to_string(),
+                    "tt_chain_token=AbCdEf123==; ttwid=1%7CAbC%7C1700000000%7Cdef".to_string()
+                ),
+            ]
+        );
+
+        let variants: Vec<_> = video
+            .variants
+            .iter()
+            .map(|it| (it.url.path(), it.height, it.size))
+            .collect();
+        assert_eq!(
+            variants,
+            [
+                ("/video/tos/720.mp4", Some(1280), Some(1687500)),
+                ("/video/tos/540.mp4", Some(960), Some(937500)),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_video_without_cookies() {
+        let client = FakeHttpClient::new().fixture(URL, "tiktok/video.html");
+        let media = resolve(&client).unwrap();
+
+        assert_eq!(
+            media[0].headers,
+            [("Referer".to_string(), "https://www.tiktok.com/".to_string())]
+        );
+    }
+
+    #[test]
+    fn resolves_photos_in_post_order() {
+        let client = FakeHttpClient::new().fixture(URL, "tiktok/photo.html");
+        let media = resolve(&client).unwrap();
+
+        // The first mirror of every picture is taken.
+        let urls: Vec<_> = media.iter().map(|it| it.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://p16-sign.tiktokcdn.com/photo-1.jpeg",
+                "https://p16-sign.tiktokcdn.com/photo-2.jpeg",
+            ]
+        );
+        assert!(media.iter().all(|it| it.kind == MediaKind::Photo));
+        // Pictures are served without the video CDN restrictions.
+        assert!(media.iter().all(|it| it.headers.is_empty()));
+        assert_eq!(media[1].author.as_deref(), Some("traveller"));
+        assert_eq!(media[1].title.as_deref(), Some("Trip"));
+    }
+
+    #[test]
+    fn reports_status_of_unavailable_videos() {
+        let client = FakeHttpClient::new().fixture(URL, "tiktok/unavailable.html");
+        assert_eq!(
+            resolve(&client).unwrap_err().to_string(),
+            "Video is unavailable, status code: 10204"
+        );
+    }
+
+    #[test]
+    fn rejects_photo_posts_without_images() {
+        let client = FakeHttpClient::new().fixture(URL, "tiktok/empty_photo.html");
+        assert_eq!(
+            resolve(&client).unwrap_err().to_string(),
+            "Photo post has no images"
+        );
+    }
+}
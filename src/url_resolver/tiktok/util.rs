@@ -0,0 +1,146 @@
+use anyhow::{anyhow, bail, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+
+lazy_static! {
+    pub static ref UNIVERSAL_DATA_REGEX: Regex = Regex::new(
+        r#"(?s)<script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">(.+?)</script>"#
+    )
+    .unwrap();
+    pub static ref CDN_COOKIE_REGEX: Regex =
+        Regex::new(r#"^\s*((?:tt_chain_token|ttwid)=[^;]+)"#).unwrap();
+}
+
+#[derive(Debug, Deserialize)]
+struct UniversalData {
+    #[serde(rename = "__DEFAULT_SCOPE__")]
+    default_scope: DefaultScope,
+}
+
+#[derive(Debug, Deserialize)]
+struct DefaultScope {
+    #[serde(rename = "webapp.video-detail")]
+    video_detail: Option<VideoDetail>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VideoDetail {
+    status_code: i64,
+    item_info: Option<ItemInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemInfo {
+    item_struct: ItemStruct,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemStruct {
+    pub desc: Option<String>,
+    pub video: Video,
+    pub author: Option<Author>,
+    pub image_post: Option<ImagePost>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    pub play_addr: Option<String>,
+    pub cover: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub duration: Option<u32>,
+    pub bitrate_info: Option<Vec<BitrateInfo>>,
+}
+
+/// Encoding of the video in a single quality.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BitrateInfo {
+    pub bitrate: Option<u64>,
+    pub play_addr: BitratePlayAddr,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BitratePlayAddr {
+    pub url_list: Vec<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Either a number or a string with a number.
+    pub data_size: Option<serde_json::Value>,
+}
+
+impl BitratePlayAddr {
+    pub fn size(&self) -> Option<u64> {
+        match self.data_size.as_ref()? {
+            serde_json::Value::Number(size) => size.as_u64(),
+            serde_json::Value::String(size) => size.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Author {
+    pub unique_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImagePost {
+    pub images: Vec<Image>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Image {
+    #[serde(rename = "imageURL")]
+    pub image_url: UrlList,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlList {
+    pub url_list: Vec<String>,
+}
+
+pub fn get_item_struct(html: &str) -> Result<ItemStruct> {
+    let capts = UNIVERSAL_DATA_REGEX.captures(html).ok_or(anyhow!(
+        "Cannot capture `__UNIVERSAL_DATA_FOR_REHYDRATION__`"
+    ))?;
+    let data: UniversalData = serde_json::from_str(&capts[1])?;
+    let video_detail = data
+        .default_scope
+        .video_detail
+        .ok_or(anyhow!("Cannot get `webapp.video-detail`"))?;
+    if video_detail.status_code != 0 {
+        bail!(
+            "Video is unavailable, status code: {}",
+            video_detail.status_code
+        );
+    }
+    video_detail
+        .item_info
+        .map(|it| it.item_struct)
+        .ok_or(anyhow!("Cannot get `itemStruct`"))
+}
+
+/// Builds the `Cookie` header from `tt_chain_token` and `ttwid` cookies set by
+/// the page, the CDN rejects requests without them.
+pub fn get_cdn_cookie(headers: &HeaderMap) -> Option<String> {
+    let cookies: Vec<_> = headers
+        .get_all("set-cookie")
+        .iter()
+        .filter_map(|it| it.to_str().ok())
+        .filter_map(|it| Some(CDN_COOKIE_REGEX.captures(it)?[1].to_string()))
+        .collect();
+    if cookies.is_empty() {
+        return None;
+    }
+    Some(cookies.join("; "))
+}
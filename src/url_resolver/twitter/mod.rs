@@ -1,44 +1,95 @@
-use anyhow::{anyhow, bail};
-use reqwest::Client;
+use anyhow::{anyhow, bail, Result};
+use futures::future::LocalBoxFuture;
 use url::Url;
 
 use self::util::DOWNLOAD_LINK_REGEX;
 
-use super::ResolveUrl;
+use super::{
+    http::{HttpClient, HttpRequest},
+    MediaKind, ResolveUrl, ResolvedMedia,
+};
 
 mod util;
 
 #[derive(Debug)]
-pub struct TwitterUrlResolver<'a> {
-    http_client: &'a Client,
+pub struct TwitterUrlResolver<'a, C: HttpClient> {
+    http_client: &'a C,
 }
 
-impl<'a> TwitterUrlResolver<'a> {
-    pub fn new(http_client: &'a Client) -> Self {
+impl<'a, C: HttpClient> TwitterUrlResolver<'a, C> {
+    pub fn new(http_client: &'a C) -> Self {
         Self { http_client }
     }
-}
 
-impl<'a> ResolveUrl<'a> for TwitterUrlResolver<'a> {
-    async fn resolve_url(&self, url: &'a str) -> anyhow::Result<url::Url> {
-        let html = self.http_client.post("https://savetwitter.net/api/ajaxSearch")
+    async fn resolve(&self, url: &str) -> anyhow::Result<Vec<ResolvedMedia>> {
+        let html = self.http_client.send(HttpRequest::post("https://savetwitter.net/api/ajaxSearch")
             .form(&[
                 ("q", url),
                 ("lang", "en")
             ])
             .header("Referer", "https://savetwitter.net/")
-            .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:122.0) Gecko/20100101 Firefox/122.0")
-            .send()
+            .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:122.0) Gecko/20100101 Firefox/122.0"))
             .await?
-            .text()
-            .await
-            .map_err(|err| anyhow!(err))?;
+            .error_for_status()?
+            .body;
         let capts = DOWNLOAD_LINK_REGEX
             .captures(&html)
             .ok_or(anyhow!("Cannot find URL"))?;
         if capts.len() < 2 {
             bail!("Cannot find URL");
         }
-        Url::parse(&capts[1]).map_err(|err| anyhow!(err))
+        let url = Url::parse(&capts[1]).map_err(|err| anyhow!(err))?;
+        Ok(vec![ResolvedMedia::new(MediaKind::Video, url)])
+    }
+}
+
+impl<C: HttpClient> ResolveUrl for TwitterUrlResolver<'_, C> {
+    fn resolve_url<'b>(&'b self, url: &'b str) -> LocalBoxFuture<'b, Result<Vec<ResolvedMedia>>> {
+        Box::pin(self.resolve(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    const URL: &str = "https://twitter.com/example/status/1700000000000000000";
+    const ENDPOINT: &str = "https://savetwitter.net/api/ajaxSearch";
+
+    fn resolve(client: &FakeHttpClient) -> Result<Vec<ResolvedMedia>> {
+        block_on(TwitterUrlResolver::new(client).resolve(URL))
+    }
+
+    /// Links are listed from the best quality, only the first is kept.
+    #[test]
+    fn resolves_best_quality_link() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "twitter/result.json");
+        let media = resolve(&client).unwrap();
+
+        assert_eq!(media.len(), 1);
+        assert_eq!(media[0].kind, MediaKind::Video);
+        assert_eq!(
+            media[0].url.as_str(),
+            "https://dl.snapcdn.app/get?token=720p-token"
+        );
+
+        let requests = client.requests.borrow();
+        let body = requests[0].body.as_deref().unwrap();
+        assert!(body.contains("lang=en"));
+        assert!(body.contains(&format!(
+            "q={}",
+            url::form_urlencoded::byte_serialize(URL.as_bytes()).collect::<String>()
+        )));
+    }
+
+    #[test]
+    fn rejects_tweets_without_video() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "twitter/empty.json");
+        let err = resolve(&client).unwrap_err();
+
+        assert_eq!(err.to_string(), "Cannot find URL");
     }
 }
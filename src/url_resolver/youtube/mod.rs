@@ -0,0 +1,249 @@
+use anyhow::{anyhow, bail, Result};
+use futures::future::LocalBoxFuture;
+use serde_json::json;
+use url::Url;
+
+use super::{
+    format::{sort_formats, FormatPolicy},
+    http::{HttpClient, HttpRequest},
+    MediaKind, ResolveUrl, ResolvedMedia, Variant,
+};
+
+mod util;
+use util::{get_video_id, PlayerResponse};
+
+static CLIENT_VERSION: &str = "19.09.37";
+static USER_AGENT: &str = "com.google.android.youtube/19.09.37 (Linux; U; Android 11) gzip";
+
+/// Resolves YouTube videos using the player response of the innertube API
+/// of the Android app, which has URLs of formats without signature ciphers.
+#[derive(Debug)]
+pub struct YouTubeUrlResolver<'a, C: HttpClient> {
+    http_client: &'a C,
+    policy: FormatPolicy,
+    /// Longer videos are rejected, in seconds.
+    max_duration: u32,
+}
+
+impl<'a, C: HttpClient> YouTubeUrlResolver<'a, C> {
+    pub fn new(http_client: &'a C, policy: FormatPolicy, max_duration: u32) -> Self {
+        Self {
+            http_client,
+            policy,
+            max_duration,
+        }
+    }
+
+    async fn get_player_response(&self, video_id: &str) -> Result<PlayerResponse> {
+        let body = json!({
+            "context": {
+                "client": {
+                    "clientName": "ANDROID",
+                    "clientVersion": CLIENT_VERSION,
+                    "androidSdkVersion": 30,
+                    "hl": "en",
+                },
+            },
+            "videoId": video_id,
+            "contentCheckOk": true,
+            "racyCheckOk": true,
+        });
+        let json = self
+            .http_client
+            .send(
+                HttpRequest::post("https://www.youtube.com/youtubei/v1/player?prettyPrint=false")
+                    .header("User-Agent", USER_AGENT)
+                    .header("X-YouTube-Client-Name", "3")
+                    .header("X-YouTube-Client-Version", CLIENT_VERSION)
+                    .json(&body),
+            )
+            .await?
+            .error_for_status()?
+            .body;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    async fn resolve(&self, url: &str) -> Result<Vec<ResolvedMedia>> {
+        let video_id = get_video_id(url)?;
+        let PlayerResponse {
+            playability_status,
+            video_details,
+            streaming_data,
+        } = self.get_player_response(&video_id).await?;
+
+        if playability_status.status != "OK" {
+            bail!(
+                "Video {video_id} is not playable: {}",
+                playability_status
+                    .reason
+                    .unwrap_or(playability_status.status)
+            );
+        }
+        let video_details = video_details.ok_or(anyhow!("Cannot get `videoDetails`"))?;
+        if video_details.is_live == Some(true) {
+            bail!("Live streams are not supported");
+        }
+        let duration = video_details
+            .length_seconds
+            .and_then(|it| it.parse::<u32>().ok());
+        if let Some(duration) = duration.filter(|it| *it > self.max_duration) {
+            bail!(
+                "Video is {duration} seconds long, only {} are allowed",
+                self.max_duration
+            );
+        }
+
+        let formats: Vec<_> = streaming_data
+            .ok_or(anyhow!("Cannot get `streamingData`"))?
+            .formats
+            .iter()
+            .filter_map(|it| it.to_format())
+            .filter(|it| it.is_playable())
+            .collect();
+        let formats = sort_formats(&formats, &self.policy);
+        let (best, rest) = formats
+            .split_first()
+            .ok_or(anyhow!("Video {video_id} has no playable formats"))?;
+
+        let mut video = ResolvedMedia::new(MediaKind::Video, Url::parse(&best.url)?);
+        video.width = best.width;
+        video.height = best.height;
+        video.size = best.size;
+        video.duration = duration;
+        video.title = video_details.title;
+        video.thumbnail = video_details
+            .thumbnail
+            .and_then(|it| it.thumbnails.into_iter().max_by_key(|it| it.width))
+            .and_then(|it| Url::parse(&it.url).ok());
+        // URLs of formats only work from the IP address that requested the
+        // player response, so they cannot be fetched by Telegram or opened by
+        // users. The headers make the worker upload the media itself.
+        video.headers = vec![("User-Agent".to_string(), USER_AGENT.to_string())];
+        video.variants = rest
+            .iter()
+            .filter(|it| it.height < best.height)
+            .filter_map(|it| {
+                Some(Variant {
+                    url: Url::parse(&it.url).ok()?,
+                    width: it.width,
+                    height: it.height,
+                    size: it.size,
+                })
+            })
+            .collect();
+
+        Ok(vec![video])
+    }
+}
+
+impl<C: HttpClient> ResolveUrl for YouTubeUrlResolver<'_, C> {
+    fn resolve_url<'b>(&'b self, url: &'b str) -> LocalBoxFuture<'b, Result<Vec<ResolvedMedia>>> {
+        Box::pin(self.resolve(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::url_resolver::http::fake::FakeHttpClient;
+
+    const ENDPOINT: &str = "https://www.youtube.com/youtubei/v1/player";
+    const URL: &str = "https://www.youtube.com/shorts/dQw4w9WgXcQ";
+
+    fn resolve(client: &FakeHttpClient, policy: FormatPolicy) -> Result<Vec<ResolvedMedia>> {
+        block_on(YouTubeUrlResolver::new(client, policy, 600).resolve(URL))
+    }
+
+    fn itag(url: &Url) -> String {
+        url.query_pairs()
+            .find(|(name, _)| name == "itag")
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn resolves_best_progressive_format() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "youtube/player.json");
+        let media = resolve(&client, FormatPolicy::HIGH).unwrap();
+
+        assert_eq!(media.len(), 1);
+        let video = &media[0];
+        assert_eq!(itag(&video.url), "22");
+        assert_eq!((video.width, video.height), (Some(720), Some(1280)));
+        assert_eq!(video.duration, Some(58));
+        assert_eq!(
+            video.title.as_deref(),
+            Some("Never Gonna Give You Up (Short)")
+        );
+        assert_eq!(
+            video.thumbnail.as_ref().map(Url::as_str),
+            Some("https://i.ytimg.com/vi/dQw4w9WgXcQ/hqdefault.jpg")
+        );
+        assert_eq!(
+            video.headers,
+            [("User-Agent".to_string(), USER_AGENT.to_string())]
+        );
+        let variants: Vec<_> = video.variants.iter().map(|it| itag(&it.url)).collect();
+        assert_eq!(variants, ["18"]);
+    }
+
+    #[test]
+    fn resolves_smallest_format_with_low_policy() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "youtube/player.json");
+        let media = resolve(&client, FormatPolicy::LOW).unwrap();
+
+        assert_eq!(itag(&media[0].url), "18");
+    }
+
+    #[test]
+    fn resolves_regular_videos_by_id() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "youtube/player.json");
+        let resolver = YouTubeUrlResolver::new(&client, FormatPolicy::HIGH, 600);
+        let media = block_on(resolver.resolve("https://youtu.be/dQw4w9WgXcQ?t=42")).unwrap();
+
+        // Signed URLs expire, inline results must not outlive them.
+        assert_eq!(media[0].expires_at, Some(1700021540));
+        let requests = client.requests.borrow();
+        let body: serde_json::Value =
+            serde_json::from_str(requests[0].body.as_deref().unwrap()).unwrap();
+        assert_eq!(body["videoId"], "dQw4w9WgXcQ");
+        assert_eq!(body["context"]["client"]["clientName"], "ANDROID");
+    }
+
+    #[test]
+    fn rejects_unplayable_videos_with_reason() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "youtube/unplayable.json");
+        let err = resolve(&client, FormatPolicy::HIGH).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Video dQw4w9WgXcQ is not playable: Sign in to confirm your age"
+        );
+    }
+
+    #[test]
+    fn rejects_live_streams() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "youtube/live.json");
+        let err = resolve(&client, FormatPolicy::HIGH).unwrap_err();
+        assert_eq!(err.to_string(), "Live streams are not supported");
+    }
+
+    #[test]
+    fn rejects_videos_longer_than_max_duration() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "youtube/long.json");
+        let err = resolve(&client, FormatPolicy::HIGH).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Video is 7200 seconds long, only 600 are allowed"
+        );
+    }
+
+    /// Only ciphered and video-only formats are offered.
+    #[test]
+    fn rejects_videos_without_progressive_formats() {
+        let client = FakeHttpClient::new().fixture(ENDPOINT, "youtube/no_formats.json");
+        let err = resolve(&client, FormatPolicy::HIGH).unwrap_err();
+        assert_eq!(err.to_string(), "Video dQw4w9WgXcQ has no playable formats");
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use url::Url;
+
+use crate::url_resolver::format::Format;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerResponse {
+    pub playability_status: PlayabilityStatus,
+    pub video_details: Option<VideoDetails>,
+    pub streaming_data: Option<StreamingData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayabilityStatus {
+    /// `OK`, `LOGIN_REQUIRED`, `UNPLAYABLE`, `ERROR` and such.
+    pub status: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoDetails {
+    pub title: Option<String>,
+    /// Seconds as a string.
+    pub length_seconds: Option<String>,
+    pub is_live: Option<bool>,
+    pub thumbnail: Option<Thumbnails>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Thumbnails {
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Thumbnail {
+    pub url: String,
+    pub width: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamingData {
+    /// Progressive formats with both video and audio. Video-only and
+    /// audio-only `adaptiveFormats` are ignored.
+    #[serde(default)]
+    pub formats: Vec<StreamFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamFormat {
+    /// Absent for formats with `signatureCipher`.
+    pub url: Option<String>,
+    /// E.g. `video/mp4; codecs="avc1.42001E, mp4a.40.2"`.
+    pub mime_type: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Bytes as a string.
+    pub content_length: Option<String>,
+}
+
+impl StreamFormat {
+    pub fn to_format(&self) -> Option<Format> {
+        let (mime_type, codecs) = self
+            .mime_type
+            .split_once(';')
+            .unwrap_or((&self.mime_type, ""));
+        let codecs: Vec<_> = codecs
+            .trim()
+            .trim_start_matches("codecs=")
+            .trim_matches('"')
+            .split(',')
+            .map(str::trim)
+            .filter(|it| !it.is_empty())
+            .collect();
+
+        // Video codec goes first, audio-only formats list just the audio one.
+        let ext = mime_type.strip_prefix("video/");
+        let (video_codec, audio_codecs) = match (ext, codecs.split_first()) {
+            (Some(_), Some((video_codec, rest))) => (Some(video_codec.to_string()), rest),
+            _ => (None, codecs.as_slice()),
+        };
+
+        Some(Format {
+            url: self.url.clone()?,
+            ext: ext.map(|it| it.to_string()),
+            video_codec,
+            has_audio: !audio_codecs.is_empty(),
+            width: self.width,
+            height: self.height,
+            size: self.content_length.as_ref().and_then(|it| it.parse().ok()),
+        })
+    }
+}
+
+/// Extracts the video ID from `/watch?v=<id>`, `/shorts/<id>`, `/live/<id>`
+/// and `youtu.be/<id>` links.
+pub fn get_video_id(url: &str) -> Result<String> {
+    let url = Url::parse(url)?;
+    let segments: Vec<_> = url
+        .path_segments()
+        .map(|it| it.filter(|it| !it.is_empty()).collect())
+        .unwrap_or_default();
+
+    let id = match (url.host_str().unwrap_or_default(), segments.as_slice()) {
+        ("youtu.be", [id, ..]) => Some(id.to_string()),
+        (_, ["shorts" | "live", id, ..]) => Some(id.to_string()),
+        (_, ["watch"]) => url
+            .query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, id)| id.into_owned()),
+        _ => None,
+    };
+
+    id.filter(|id| {
+        id.len() == 11
+            && id
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+    })
+    .ok_or(anyhow!("Cannot get video ID: {url}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_format(mime_type: &str) -> StreamFormat {
+        StreamFormat {
+            url: Some("https://rr3---sn-4g5e6nsz.googlevideo.com/videoplayback".to_string()),
+            mime_type: mime_type.to_string(),
+            width: None,
+            height: None,
+            content_length: None,
+        }
+    }
+
+    #[test]
+    fn gets_video_id() {
+        let cases = [
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ", "dQw4w9WgXcQ"),
+            (
+                "https://m.youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=42",
+                "dQw4w9WgXcQ",
+            ),
+            ("https://www.youtube.com/shorts/a-B_c1D2e3F", "a-B_c1D2e3F"),
+            (
+                "https://youtube.com/shorts/a-B_c1D2e3F?si=xyz",
+                "a-B_c1D2e3F",
+            ),
+            ("https://www.youtube.com/live/jfKfPfyJRdk", "jfKfPfyJRdk"),
+            ("https://youtu.be/dQw4w9WgXcQ?si=abc", "dQw4w9WgXcQ"),
+        ];
+        for (url, id) in cases {
+            assert_eq!(get_video_id(url).unwrap(), id, "{url}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_video_ids() {
+        let cases = [
+            "https://www.youtube.com/watch",
+            "https://www.youtube.com/watch?v=",
+            "https://www.youtube.com/watch?v=dQw4w9WgXc",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQQ",
+            "https://www.youtube.com/shorts/dQw4w9WgX%20Q",
+            "https://www.youtube.com/shorts/dQw4w9WgX.Q",
+            "https://youtu.be/",
+            "https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw",
+            "not a url",
+        ];
+        for url in cases {
+            assert!(get_video_id(url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn parses_player_response() {
+        let path = format!(
+            "{}/tests/fixtures/youtube/player.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let response: PlayerResponse =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+        let formats: Vec<_> = response
+            .streaming_data
+            .unwrap()
+            .formats
+            .iter()
+            .filter_map(StreamFormat::to_format)
+            .collect();
+        // The format with `signatureCipher` has no URL and is skipped.
+        assert_eq!(formats.len(), 2);
+
+        let format = &formats[0];
+        assert_eq!(format.ext.as_deref(), Some("mp4"));
+        assert_eq!(format.video_codec.as_deref(), Some("avc1.42001E"));
+        assert!(format.has_audio);
+        assert_eq!((format.width, format.height), (Some(360), Some(640)));
+        assert_eq!(format.size, Some(3650000));
+        assert!(format.is_playable());
+
+        assert_eq!(formats[1].size, None);
+        assert!(formats[1].is_playable());
+    }
+
+    #[test]
+    fn parses_mime_types() {
+        let cases = [
+            (
+                r#"video/mp4; codecs="avc1.64001F, mp4a.40.2""#,
+                Some("mp4"),
+                Some("avc1.64001F"),
+                true,
+            ),
+            (
+                r#"video/webm; codecs="vp9, opus""#,
+                Some("webm"),
+                Some("vp9"),
+                true,
+            ),
+            (
+                r#"video/mp4; codecs="av01.0.05M.08""#,
+                Some("mp4"),
+                Some("av01.0.05M.08"),
+                false,
+            ),
+            (r#"audio/mp4; codecs="mp4a.40.2""#, None, None, true),
+            ("video/mp4", Some("mp4"), None, false),
+        ];
+        for (mime_type, ext, video_codec, has_audio) in cases {
+            let format = stream_format(mime_type).to_format().unwrap();
+            assert_eq!(format.ext.as_deref(), ext, "{mime_type}");
+            assert_eq!(format.video_codec.as_deref(), video_codec, "{mime_type}");
+            assert_eq!(format.has_audio, has_audio, "{mime_type}");
+        }
+    }
+}
